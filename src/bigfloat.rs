@@ -0,0 +1,341 @@
+use num_bigint::BigInt;
+use num_traits::{pow, Signed, Zero};
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+use crate::floatexp::FloatExp;
+
+// Arbitrary precision decimal number: mantissa * 10^exponent
+// Decimal so that coordinates given as strings round trip exactly
+#[derive(Clone, Debug)]
+pub struct BigFloat {
+    mantissa: BigInt,
+    exponent: i64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseBigFloatError;
+
+// Largest decimal exponent magnitude parsed, far beyond any zoom depth but
+// keeping decimal representations short enough to build
+pub const MAX_EXPONENT: i64 = 10_000;
+
+fn ten_pow(n: i64) -> BigInt {
+    pow(BigInt::from(10), n as usize)
+}
+
+impl BigFloat {
+    pub fn zero() -> BigFloat {
+        BigFloat {
+            mantissa: BigInt::zero(),
+            exponent: 0,
+        }
+    }
+
+    // Exact conversion: m * 2^-e = m * 5^e * 10^-e
+    fn from_binary(mantissa: BigInt, exponent: i64) -> BigFloat {
+        if exponent >= 0 {
+            BigFloat {
+                mantissa: mantissa << exponent as usize,
+                exponent: 0,
+            }
+        } else {
+            BigFloat {
+                mantissa: mantissa * pow(BigInt::from(5), -exponent as usize),
+                exponent,
+            }
+        }
+    }
+
+    fn from_f64_exp2(value: f64, exponent: i64) -> BigFloat {
+        if value == 0. || !value.is_finite() {
+            return BigFloat::zero();
+        }
+        let bits = value.to_bits();
+        let biased_exponent = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = bits & 0xf_ffff_ffff_ffff;
+        let (significand, binary_exponent) = if biased_exponent == 0 {
+            (fraction, -1074)
+        } else {
+            (fraction | 0x10_0000_0000_0000, biased_exponent - 1075)
+        };
+        let mut mantissa = BigInt::from(significand);
+        if value < 0. {
+            mantissa = -mantissa;
+        }
+        BigFloat::from_binary(mantissa, binary_exponent + exponent)
+    }
+
+    pub fn from_f64(value: f64) -> BigFloat {
+        BigFloat::from_f64_exp2(value, 0)
+    }
+
+    pub fn from_floatexp(value: FloatExp) -> BigFloat {
+        BigFloat::from_f64_exp2(value.mantissa(), value.exponent())
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // Parsing the decimal representation is correctly rounded
+        self.to_string().parse().unwrap_or(0.)
+    }
+
+    pub fn to_floatexp(&self) -> FloatExp {
+        // Only the 17 leading digits matter for the f64 mantissa
+        let digits = self.mantissa.abs().to_str_radix(10);
+        let kept = digits.len().min(17);
+        let mut mantissa: f64 = digits[..kept].parse().unwrap_or(0.);
+        if self.mantissa.is_negative() {
+            mantissa = -mantissa;
+        }
+        FloatExp::from_f64(mantissa)
+            * FloatExp::from_f64(10.).powi(self.exponent + (digits.len() - kept) as i64)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa.is_zero()
+    }
+
+    // Drop every digit below 10^exponent
+    pub fn truncate(&self, exponent: i64) -> BigFloat {
+        if self.exponent >= exponent {
+            return self.clone();
+        }
+        BigFloat {
+            mantissa: &self.mantissa / ten_pow(exponent - self.exponent),
+            exponent,
+        }
+    }
+
+    fn aligned(&self, other: &BigFloat) -> (BigInt, BigInt, i64) {
+        if self.exponent > other.exponent {
+            (
+                &self.mantissa * ten_pow(self.exponent - other.exponent),
+                other.mantissa.clone(),
+                other.exponent,
+            )
+        } else {
+            (
+                self.mantissa.clone(),
+                &other.mantissa * ten_pow(other.exponent - self.exponent),
+                self.exponent,
+            )
+        }
+    }
+}
+
+impl Add for &BigFloat {
+    type Output = BigFloat;
+
+    fn add(self, other: &BigFloat) -> BigFloat {
+        let (a, b, exponent) = self.aligned(other);
+        BigFloat {
+            mantissa: a + b,
+            exponent,
+        }
+    }
+}
+
+impl Sub for &BigFloat {
+    type Output = BigFloat;
+
+    fn sub(self, other: &BigFloat) -> BigFloat {
+        let (a, b, exponent) = self.aligned(other);
+        BigFloat {
+            mantissa: a - b,
+            exponent,
+        }
+    }
+}
+
+impl Mul for &BigFloat {
+    type Output = BigFloat;
+
+    fn mul(self, other: &BigFloat) -> BigFloat {
+        BigFloat {
+            mantissa: &self.mantissa * &other.mantissa,
+            exponent: self.exponent + other.exponent,
+        }
+    }
+}
+
+impl Neg for &BigFloat {
+    type Output = BigFloat;
+
+    fn neg(self) -> BigFloat {
+        BigFloat {
+            mantissa: -&self.mantissa,
+            exponent: self.exponent,
+        }
+    }
+}
+
+impl PartialEq for BigFloat {
+    fn eq(&self, other: &BigFloat) -> bool {
+        let (a, b, _) = self.aligned(other);
+        a == b
+    }
+}
+
+impl fmt::Display for BigFloat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.mantissa.is_zero() {
+            return write!(f, "0");
+        }
+        let digits = self.mantissa.abs().to_str_radix(10);
+        let sign = if self.mantissa.is_negative() { "-" } else { "" };
+        if self.exponent >= 0 {
+            return write!(
+                f,
+                "{}{}{}",
+                sign,
+                digits,
+                "0".repeat(self.exponent as usize)
+            );
+        }
+        let point = digits.len() as i64 + self.exponent;
+        let (integer, fraction) = if point > 0 {
+            (
+                digits[..point as usize].to_string(),
+                digits[point as usize..].to_string(),
+            )
+        } else {
+            ("0".to_string(), "0".repeat(-point as usize) + &digits)
+        };
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            write!(f, "{}{}", sign, integer)
+        } else {
+            write!(f, "{}{}.{}", sign, integer, fraction)
+        }
+    }
+}
+
+impl FromStr for BigFloat {
+    type Err = ParseBigFloatError;
+
+    // Accepts [-+]digits[.digits][e[-+]digits]
+    fn from_str(s: &str) -> Result<BigFloat, ParseBigFloatError> {
+        let s = s.trim();
+        let (number, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (
+                &s[..i],
+                s[i + 1..]
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|exponent| exponent.abs() <= MAX_EXPONENT)
+                    .ok_or(ParseBigFloatError)?,
+            ),
+            None => (s, 0),
+        };
+        let (negative, number) = match number.chars().next() {
+            Some('-') => (true, &number[1..]),
+            Some('+') => (false, &number[1..]),
+            _ => (false, number),
+        };
+        let (integer, fraction) = match number.find('.') {
+            Some(i) => (&number[..i], &number[i + 1..]),
+            None => (number, ""),
+        };
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(ParseBigFloatError);
+        }
+        let exponent = exponent - fraction.len() as i64;
+        if exponent.abs() > MAX_EXPONENT {
+            return Err(ParseBigFloatError);
+        }
+        let digits = format!("{}{}", integer, fraction);
+        let mut mantissa = BigInt::parse_bytes(digits.as_bytes(), 10).ok_or(ParseBigFloatError)?;
+        if negative {
+            mantissa = -mantissa;
+        }
+        Ok(BigFloat { mantissa, exponent })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BigPoint {
+    pub x: BigFloat,
+    pub y: BigFloat,
+}
+
+impl BigPoint {
+    pub fn new(x: BigFloat, y: BigFloat) -> BigPoint {
+        BigPoint { x, y }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(s: &str) -> String {
+        s.parse::<BigFloat>().unwrap().to_string()
+    }
+
+    #[test]
+    fn display_round_trips_from_str() {
+        for s in [
+            "-0.743643887037158704752191506114774",
+            "0.131825904205311970493132056385139",
+            "1.5",
+            "-42",
+            "0",
+        ] {
+            assert_eq!(round_trip(s), s);
+        }
+        assert_eq!(round_trip("1e-400"), format!("0.{}1", "0".repeat(399)));
+        assert_eq!(round_trip("+2.50e3"), "2500");
+        assert_eq!(round_trip("-.25"), "-0.25");
+    }
+
+    #[test]
+    fn from_str_rejects_invalid_numbers() {
+        for s in [
+            "",
+            "-",
+            ".",
+            "1.2.3",
+            "1e",
+            "abc",
+            "1e999999999",
+            "1e-20000",
+        ] {
+            assert_eq!(s.parse::<BigFloat>().unwrap_err(), ParseBigFloatError);
+        }
+    }
+
+    #[test]
+    fn arithmetic_is_exact() {
+        let a: BigFloat = "0.1".parse().unwrap();
+        let b: BigFloat = "0.2".parse().unwrap();
+        assert_eq!((&a + &b).to_string(), "0.3");
+        assert_eq!((&a - &b).to_string(), "-0.1");
+        assert_eq!((&a * &b).to_string(), "0.02");
+        let c: BigFloat = "1e-400".parse().unwrap();
+        assert_eq!(&(&a + &c) - &a, c);
+    }
+
+    #[test]
+    fn converts_from_and_to_binary() {
+        assert_eq!(BigFloat::from_f64(0.1).to_f64(), 0.1);
+        assert_eq!(BigFloat::from_f64(-0.375).to_string(), "-0.375");
+        let tiny = FloatExp::new(1.5, -2000);
+        let converted = BigFloat::from_floatexp(tiny).to_floatexp();
+        assert!(((converted / tiny).to_f64() - 1.).abs() < 1e-14);
+        assert_eq!(
+            "1e-400"
+                .parse::<BigFloat>()
+                .unwrap()
+                .to_floatexp()
+                .log10()
+                .round(),
+            -400.
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use wasm_bindgen::prelude::*;
+
+// Hues are in degrees, other components in [0, 1] except CIE L in [0, 100]
+// and its a, b, chroma on the same scale:
+// Rgb: r, g, b
+// Hsl: h, s, l
+// Hsv: h, s, v
+// Hwb: h, w, b
+// Lch: L, C, h (CIE)
+// Oklab: L, a, b
+// Oklch: L, C, h
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorSpace {
+    Rgb,
+    Hsl,
+    Hsv,
+    Hwb,
+    Lch,
+    Oklab,
+    Oklch,
+}
+
+// Lightness and chroma of the hue circle in the lch spaces
+const LCH_CIRCLE: (f64, f64) = (65., 55.);
+const OKLCH_CIRCLE: (f64, f64) = (0.72, 0.14);
+const GAMUT_STEPS: usize = 24;
+const ACHROMATIC: f64 = 1e-6;
+
+fn to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(c: f64) -> f64 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+fn hue([r, g, b]: [f64; 3], max: f64, delta: f64) -> f64 {
+    if delta == 0. {
+        0.
+    } else if max == r {
+        60. * ((g - b) / delta).rem_euclid(6.)
+    } else if max == g {
+        60. * ((b - r) / delta + 2.)
+    } else {
+        60. * ((r - g) / delta + 4.)
+    }
+}
+
+fn rgb_to_hsv(rgb: [f64; 3]) -> [f64; 3] {
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    let delta = max - rgb[0].min(rgb[1]).min(rgb[2]);
+    let s = if max == 0. { 0. } else { delta / max };
+    [hue(rgb, max, delta), s, max]
+}
+
+fn hsv_to_rgb([h, s, v]: [f64; 3]) -> [f64; 3] {
+    let h = h.rem_euclid(360.) / 60.;
+    let c = v * s;
+    let x = c * (1. - (h % 2. - 1.).abs());
+    let (r, g, b) = match h as usize {
+        0 => (c, x, 0.),
+        1 => (x, c, 0.),
+        2 => (0., c, x),
+        3 => (0., x, c),
+        4 => (x, 0., c),
+        _ => (c, 0., x),
+    };
+    [r + v - c, g + v - c, b + v - c]
+}
+
+fn rgb_to_hsl(rgb: [f64; 3]) -> [f64; 3] {
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    let min = rgb[0].min(rgb[1]).min(rgb[2]);
+    let delta = max - min;
+    let l = (max + min) / 2.;
+    let s = if delta == 0. {
+        0.
+    } else {
+        delta / (1. - (2. * l - 1.).abs())
+    };
+    [hue(rgb, max, delta), s, l]
+}
+
+fn hsl_to_rgb([h, s, l]: [f64; 3]) -> [f64; 3] {
+    let c = (1. - (2. * l - 1.).abs()) * s;
+    hsv_to_rgb([
+        h,
+        if l + c / 2. == 0. {
+            0.
+        } else {
+            c / (l + c / 2.)
+        },
+        l + c / 2.,
+    ])
+}
+
+fn rgb_to_hwb(rgb: [f64; 3]) -> [f64; 3] {
+    let [h, s, v] = rgb_to_hsv(rgb);
+    [h, (1. - s) * v, 1. - v]
+}
+
+fn hwb_to_rgb([h, w, b]: [f64; 3]) -> [f64; 3] {
+    if w + b >= 1. {
+        let gray = w / (w + b);
+        return [gray; 3];
+    }
+    hsv_to_rgb([h, 1. - w / (1. - b), 1. - b])
+}
+
+fn linear_to_lab(rgb: [f64; 3]) -> [f64; 3] {
+    let f = |t: f64| {
+        if t > 216. / 24389. {
+            t.cbrt()
+        } else {
+            t * 24389. / 27. / 116. + 16. / 116.
+        }
+    };
+    let [r, g, b] = rgb;
+    let x = f((0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47);
+    let y = f(0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b);
+    let z = f((0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / 1.088_83);
+    [116. * y - 16., 500. * (x - y), 200. * (y - z)]
+}
+
+fn lab_to_linear([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let f = |t: f64| {
+        if t > 6. / 29. {
+            t.powi(3)
+        } else {
+            (116. * t - 16.) * 27. / 24389.
+        }
+    };
+    let y = (l + 16.) / 116.;
+    let (x, y, z) = (0.950_47 * f(y + a / 500.), f(y), 1.088_83 * f(y - b / 200.));
+    [
+        3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z,
+        0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+    ]
+}
+
+fn linear_to_oklab([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let l = (0.412_221_470_8 * r + 0.536_332_536_3 * g + 0.051_445_992_9 * b).cbrt();
+    let m = (0.211_903_498_2 * r + 0.680_699_545_1 * g + 0.107_396_956_6 * b).cbrt();
+    let s = (0.088_302_461_9 * r + 0.281_718_837_6 * g + 0.629_978_700_5 * b).cbrt();
+    [
+        0.210_454_255_3 * l + 0.793_617_785 * m - 0.004_072_046_8 * s,
+        1.977_998_495_1 * l - 2.428_592_205 * m + 0.450_593_709_9 * s,
+        0.025_904_037_1 * l + 0.782_771_766_2 * m - 0.808_675_766 * s,
+    ]
+}
+
+fn oklab_to_linear([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let l_ = (l + 0.396_337_777_4 * a + 0.215_803_757_3 * b).powi(3);
+    let m = (l - 0.105_561_345_8 * a - 0.063_854_172_8 * b).powi(3);
+    let s = (l - 0.089_484_177_5 * a - 1.291_485_548 * b).powi(3);
+    [
+        4.076_741_662_1 * l_ - 3.307_711_591_3 * m + 0.230_969_929_2 * s,
+        -1.268_438_004_6 * l_ + 2.609_757_401_1 * m - 0.341_319_396_5 * s,
+        -0.004_196_086_3 * l_ - 0.703_418_614_7 * m + 1.707_614_701 * s,
+    ]
+}
+
+fn to_polar([l, a, b]: [f64; 3]) -> [f64; 3] {
+    [l, a.hypot(b), b.atan2(a).to_degrees().rem_euclid(360.)]
+}
+
+fn from_polar([l, c, h]: [f64; 3]) -> [f64; 3] {
+    let h = h.to_radians();
+    [l, c * h.cos(), c * h.sin()]
+}
+
+fn in_gamut(rgb: [f64; 3]) -> bool {
+    rgb.iter().all(|c| (-1e-7..=1. + 1e-7).contains(c))
+}
+
+// Out of gamut colours keep their lightness and hue, chroma is reduced
+// until they fit in srgb
+fn map_gamut(lab: [f64; 3], to_linear: fn([f64; 3]) -> [f64; 3]) -> [f64; 3] {
+    let mut rgb = to_linear(lab);
+    if !in_gamut(rgb) {
+        let (mut low, mut high) = (0., 1.);
+        for _ in 0..GAMUT_STEPS {
+            let k = (low + high) / 2.;
+            if in_gamut(to_linear([lab[0], lab[1] * k, lab[2] * k])) {
+                low = k;
+            } else {
+                high = k;
+            }
+        }
+        rgb = to_linear([lab[0], lab[1] * low, lab[2] * low]);
+    }
+    [
+        from_linear(rgb[0].clamp(0., 1.)),
+        from_linear(rgb[1].clamp(0., 1.)),
+        from_linear(rgb[2].clamp(0., 1.)),
+    ]
+}
+
+impl ColorSpace {
+    // Rgb components are in [0, 1]
+    pub fn from_rgb(self, rgb: [f64; 3]) -> [f64; 3] {
+        let linear = || [to_linear(rgb[0]), to_linear(rgb[1]), to_linear(rgb[2])];
+        match self {
+            ColorSpace::Rgb => rgb,
+            ColorSpace::Hsl => rgb_to_hsl(rgb),
+            ColorSpace::Hsv => rgb_to_hsv(rgb),
+            ColorSpace::Hwb => rgb_to_hwb(rgb),
+            ColorSpace::Lch => to_polar(linear_to_lab(linear())),
+            ColorSpace::Oklab => linear_to_oklab(linear()),
+            ColorSpace::Oklch => to_polar(linear_to_oklab(linear())),
+        }
+    }
+
+    pub fn to_rgb(self, color: [f64; 3]) -> [f64; 3] {
+        let rgb = match self {
+            ColorSpace::Rgb => color,
+            ColorSpace::Hsl => hsl_to_rgb(color),
+            ColorSpace::Hsv => hsv_to_rgb(color),
+            ColorSpace::Hwb => hwb_to_rgb(color),
+            ColorSpace::Lch => return map_gamut(from_polar(color), lab_to_linear),
+            ColorSpace::Oklab => return map_gamut(color, oklab_to_linear),
+            ColorSpace::Oklch => return map_gamut(from_polar(color), oklab_to_linear),
+        };
+        [
+            rgb[0].clamp(0., 1.),
+            rgb[1].clamp(0., 1.),
+            rgb[2].clamp(0., 1.),
+        ]
+    }
+
+    fn hue_index(self) -> Option<usize> {
+        match self {
+            ColorSpace::Hsl | ColorSpace::Hsv | ColorSpace::Hwb => Some(0),
+            ColorSpace::Lch | ColorSpace::Oklch => Some(2),
+            ColorSpace::Rgb | ColorSpace::Oklab => None,
+        }
+    }
+
+    // Grays have no meaningful hue
+    fn is_achromatic(self, color: [f64; 3]) -> bool {
+        match self {
+            ColorSpace::Hsl | ColorSpace::Hsv => color[1] < ACHROMATIC,
+            ColorSpace::Hwb => color[1] + color[2] > 1. - ACHROMATIC,
+            ColorSpace::Lch | ColorSpace::Oklch => color[1] < ACHROMATIC,
+            ColorSpace::Rgb | ColorSpace::Oklab => false,
+        }
+    }
+
+    // Makes hues of consecutive colours interpolate along the shortest arc,
+    // grays taking the hue of their neighbours
+    pub fn unwrap_hues(self, colors: &mut [[f64; 3]]) {
+        let index = match self.hue_index() {
+            Some(index) => index,
+            None => return,
+        };
+        let chromatic: Vec<bool> = colors.iter().map(|c| !self.is_achromatic(*c)).collect();
+        for i in 0..colors.len() {
+            if !chromatic[i] {
+                let neighbour = (i + 1..colors.len())
+                    .chain((0..i).rev())
+                    .find(|&j| chromatic[j]);
+                if let Some(j) = neighbour {
+                    colors[i][index] = colors[j][index];
+                }
+            }
+        }
+        for i in 1..colors.len() {
+            let delta = (colors[i][index] - colors[i - 1][index] + 180.).rem_euclid(360.) - 180.;
+            colors[i][index] = colors[i - 1][index] + delta;
+        }
+    }
+
+    // Colour of the given hue with hwb like whiteness and blackness. The hs*
+    // spaces map them on their own components, the lch spaces go around a
+    // circle of constant lightness and chroma. Rgb and Oklab have no hue and
+    // take the ones of hwb and Oklch.
+    pub fn hue(self, hue: f64, whiteness: f64, blackness: f64) -> [f64; 3] {
+        let mut w = whiteness.clamp(0., 1.);
+        let mut b = blackness.clamp(0., 1.);
+        if w + b > 1. {
+            let sum = w + b;
+            w /= sum;
+            b /= sum;
+        }
+        let (space, (lightness, chroma), white) = match self {
+            ColorSpace::Lch => (ColorSpace::Lch, LCH_CIRCLE, 100.),
+            ColorSpace::Oklab | ColorSpace::Oklch => (ColorSpace::Oklch, OKLCH_CIRCLE, 1.),
+            // Whiteness lightens and blackness darkens, both desaturate
+            ColorSpace::Hsl => return hsl_to_rgb([hue, 1. - w - b, (1. + w - b) / 2.]),
+            // Whiteness desaturates and blackness lowers the value
+            ColorSpace::Hsv => return hsv_to_rgb([hue, 1. - w, 1. - b]),
+            ColorSpace::Rgb | ColorSpace::Hwb => return hwb_to_rgb([hue, w, b]),
+        };
+        space.to_rgb([
+            lightness + (white - lightness) * w - lightness * b,
+            chroma * (1. - w - b),
+            hue,
+        ])
+    }
+}
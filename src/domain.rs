@@ -1,14 +1,18 @@
+use crate::bigfloat::{BigFloat, BigPoint, MAX_EXPONENT};
+use crate::floatexp::FloatExp;
 use crate::point::Point;
 
+// Number of digits kept below the scale magnitude in origin coordinates
+const EXTRA_DIGITS: i64 = 20;
+
 pub struct DomainIterator<'a> {
-    x: usize,
-    y: usize,
+    index: usize,
     domain: &'a Domain,
 }
 
 impl<'a> DomainIterator<'a> {
-    fn new(domain: &'a Domain) -> DomainIterator {
-        DomainIterator { x: 0, y: 0, domain }
+    fn new(domain: &'a Domain) -> DomainIterator<'a> {
+        DomainIterator { index: 0, domain }
     }
 }
 
@@ -16,32 +20,25 @@ impl<'a> Iterator for DomainIterator<'a> {
     type Item = Point;
 
     fn next(&mut self) -> Option<Point> {
-        if self.x as f64 >= self.domain.size.x {
-            self.x = 0;
-            self.y += 1;
-            if self.y as f64 >= self.domain.size.y {
-                return None;
-            }
+        if self.index as f64 >= self.domain.size.x * self.domain.size.y {
+            return None;
         }
-        self.x += 1;
-
-        Some(
-            self.domain
-                .project(Point::new(self.x as f64, self.y as f64)),
-        )
+        let point = self.domain.point(self.index);
+        self.index += 1;
+        Some(self.domain.position(point))
     }
 }
 
 pub struct Domain {
-    pub origin: Point,
-    pub scale: f64,
+    pub origin: BigPoint,
+    pub scale: FloatExp,
     pub size: Point, // contains canvas width and height
 }
 
 impl Domain {
     pub fn new(size: Point) -> Domain {
-        let origin = Point::new(0., 0.);
-        let scale = 1.;
+        let origin = BigPoint::new(BigFloat::zero(), BigFloat::zero());
+        let scale = FloatExp::from_f64(1.);
         Domain {
             size,
             origin,
@@ -53,42 +50,87 @@ impl Domain {
         self.size = size;
     }
 
-    pub fn iter(&self) -> DomainIterator {
-        DomainIterator::new(&self)
+    // Iterates over pixel positions relative to the origin in scale units
+    pub fn iter(&self) -> DomainIterator<'_> {
+        DomainIterator::new(self)
+    }
+
+    // Canvas point at the centre of the pixel at index, pixel x covering
+    // [x, x + 1)
+    pub fn point(&self, index: usize) -> Point {
+        let width = self.size.x as usize;
+        Point::new((index % width) as f64 + 0.5, (index / width) as f64 + 0.5)
+    }
+
+    // Pixel covering the canvas point, if in the canvas
+    pub fn index(&self, point: Point) -> Option<usize> {
+        let (x, y) = (point.x.floor(), point.y.floor());
+        if x < 0. || y < 0. || x >= self.size.x || y >= self.size.y {
+            return None;
+        }
+        Some(y as usize * self.size.x as usize + x as usize)
+    }
+
+    // Origin rounded to f64, only meaningful for shallow zooms
+    pub fn center(&self) -> Point {
+        Point::new(self.origin.x.to_f64(), self.origin.y.to_f64())
     }
 
-    fn scale_point(&self) -> Point {
+    // Lowest decimal exponent kept in origin at the current scale
+    pub fn precision(&self) -> i64 {
+        let magnitude = self.scale.log10().floor();
+        // Degenerate scales mustn't ask for endless digits
+        magnitude.clamp(-MAX_EXPONENT as f64, MAX_EXPONENT as f64) as i64 - EXTRA_DIGITS
+    }
+
+    fn aspect(&self) -> Point {
         if self.size.x > self.size.y {
-            Point::new(self.scale * (self.size.x / self.size.y), self.scale)
+            Point::new(self.size.x / self.size.y, 1.)
         } else {
-            Point::new(self.scale, self.scale * (self.size.y / self.size.x))
+            Point::new(1., self.size.y / self.size.x)
         }
     }
 
-    fn projected_origin(&self) -> Point {
-        self.origin - self.scale_point()
+    // Offset from origin of the given canvas point, divided by scale
+    pub fn position(&self, point: Point) -> Point {
+        let two = Point::new(2., 2.);
+        (point * two / self.size - Point::new(1., 1.)) * self.aspect()
     }
 
-    fn project(&self, point: Point) -> Point {
-        let two = Point::new(2., 2.);
-        self.projected_origin() + point * two * self.scale_point() / self.size
+    fn move_origin(&mut self, position: Point) {
+        let precision = self.precision();
+        let x = FloatExp::from_f64(position.x) * self.scale;
+        let y = FloatExp::from_f64(position.y) * self.scale;
+        self.origin = BigPoint::new(
+            (&self.origin.x + &BigFloat::from_floatexp(x)).truncate(precision),
+            (&self.origin.y + &BigFloat::from_floatexp(y)).truncate(precision),
+        );
     }
 
-    pub fn change(&mut self, origin: Point, scale: f64) {
+    pub fn change(&mut self, origin: BigPoint, scale: FloatExp) {
         self.origin = origin;
         self.scale = scale;
     }
 
     pub fn shift(&mut self, point: Point) {
-        self.origin += self.project(point) - self.projected_origin()
+        let two = Point::new(2., 2.);
+        self.move_origin(point * two * self.aspect() / self.size)
+    }
+
+    // Multiplies the scale by ratio keeping the canvas point center in place
+    pub fn scale_around(&mut self, ratio: f64, center: Point) {
+        let keep = Point::new(1. - ratio, 1. - ratio);
+        self.move_origin(self.position(center) * keep);
+        self.scale = self.scale * FloatExp::from_f64(ratio);
     }
 
     pub fn zoom(&mut self, factor: f64, center: Point) {
-        let delta = self.project(Point::new(factor, factor)) - self.projected_origin();
+        let two = Point::new(2., 2.);
+        // Relative scale change along each axis
+        let delta = Point::new(factor, factor) * two * self.aspect() / self.size;
 
-        self.origin -=
-            delta * (self.project(center) - self.origin) / Point::new(self.scale, self.scale);
+        self.move_origin(Point::new(0., 0.) - delta * self.position(center));
 
-        self.scale += delta.x.min(delta.y);
+        self.scale = self.scale + FloatExp::from_f64(delta.x.min(delta.y)) * self.scale;
     }
 }
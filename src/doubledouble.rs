@@ -0,0 +1,214 @@
+use num_complex::Complex;
+use num_traits::{Num, One, Zero};
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+
+use crate::bigfloat::{BigFloat, ParseBigFloatError};
+
+// Unevaluated sum of two f64 giving about 106 bits of mantissa
+// Algorithms from Bailey's QD library, products use Dekker's split rather
+// than fma which is emulated (and slow) in wasm
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DoubleDouble {
+    hi: f64,
+    lo: f64,
+}
+
+pub type DoubleDoubleComplex = Complex<DoubleDouble>;
+
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    (s, b - (s - a))
+}
+
+fn split(a: f64) -> (f64, f64) {
+    let t = 134_217_729. * a; // 2^27 + 1
+    let hi = t - (t - a);
+    (hi, a - hi)
+}
+
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    let (ah, al) = split(a);
+    let (bh, bl) = split(b);
+    (p, ((ah * bh - p) + ah * bl + al * bh) + al * bl)
+}
+
+impl DoubleDouble {
+    pub fn new(hi: f64, lo: f64) -> DoubleDouble {
+        let (hi, lo) = quick_two_sum(hi, lo);
+        DoubleDouble { hi, lo }
+    }
+
+    pub fn from_f64(value: f64) -> DoubleDouble {
+        DoubleDouble { hi: value, lo: 0. }
+    }
+
+    pub fn from_bigfloat(value: &BigFloat) -> DoubleDouble {
+        let hi = value.to_f64();
+        let lo = (value - &BigFloat::from_f64(hi)).to_f64();
+        DoubleDouble::new(hi, lo)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+
+    pub fn abs(self) -> DoubleDouble {
+        if self.hi < 0. {
+            -self
+        } else {
+            self
+        }
+    }
+
+    fn trunc(self) -> DoubleDouble {
+        let hi = self.hi.trunc();
+        if hi == self.hi {
+            DoubleDouble::new(hi, self.lo.trunc())
+        } else {
+            DoubleDouble::from_f64(hi)
+        }
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: DoubleDouble) -> DoubleDouble {
+        let (s1, s2) = two_sum(self.hi, other.hi);
+        let (t1, t2) = two_sum(self.lo, other.lo);
+        let (s1, s2) = quick_two_sum(s1, s2 + t1);
+        DoubleDouble::new(s1, s2 + t2)
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn sub(self, other: DoubleDouble) -> DoubleDouble {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        let (p1, p2) = two_prod(self.hi, other.hi);
+        DoubleDouble::new(p1, p2 + (self.hi * other.lo + self.lo * other.hi))
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn div(self, other: DoubleDouble) -> DoubleDouble {
+        let q1 = self.hi / other.hi;
+        let r = self - other * DoubleDouble::from_f64(q1);
+        let q2 = r.hi / other.hi;
+        let r = r - other * DoubleDouble::from_f64(q2);
+        let q3 = r.hi / other.hi;
+        DoubleDouble::new(q1, q2) + DoubleDouble::from_f64(q3)
+    }
+}
+
+impl Rem for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn rem(self, other: DoubleDouble) -> DoubleDouble {
+        self - other * (self / other).trunc()
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn neg(self) -> DoubleDouble {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+impl PartialOrd for DoubleDouble {
+    fn partial_cmp(&self, other: &DoubleDouble) -> Option<Ordering> {
+        match self.hi.partial_cmp(&other.hi)? {
+            Ordering::Equal => self.lo.partial_cmp(&other.lo),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+impl Zero for DoubleDouble {
+    fn zero() -> DoubleDouble {
+        DoubleDouble::from_f64(0.)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.hi == 0.
+    }
+}
+
+impl One for DoubleDouble {
+    fn one() -> DoubleDouble {
+        DoubleDouble::from_f64(1.)
+    }
+}
+
+impl Num for DoubleDouble {
+    type FromStrRadixErr = ParseBigFloatError;
+
+    fn from_str_radix(s: &str, radix: u32) -> Result<DoubleDouble, ParseBigFloatError> {
+        if radix != 10 {
+            return Err(ParseBigFloatError);
+        }
+        Ok(DoubleDouble::from_bigfloat(&s.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(value: DoubleDouble) -> BigFloat {
+        &BigFloat::from_f64(value.hi) + &BigFloat::from_f64(value.lo)
+    }
+
+    // Exact difference, relative to the expected value
+    fn error(value: DoubleDouble, expected: &BigFloat) -> f64 {
+        (&big(value) - expected).to_f64() / expected.to_f64()
+    }
+
+    #[test]
+    fn keeps_more_digits_than_f64() {
+        let third = DoubleDouble::one() / DoubleDouble::from_f64(3.);
+        let expected: BigFloat = format!("0.{}", "3".repeat(40)).parse().unwrap();
+        assert!(error(third, &expected).abs() < 1e-31);
+        let sum = DoubleDouble::from_f64(1.) + DoubleDouble::from_f64(1e-20);
+        assert_eq!(sum.to_f64(), 1.);
+        assert_eq!((sum - DoubleDouble::one()).to_f64(), 1e-20);
+    }
+
+    #[test]
+    fn arithmetic_is_close_to_exact() {
+        let a =
+            DoubleDouble::from_bigfloat(&"-0.743643887037158704752191506114774".parse().unwrap());
+        let b =
+            DoubleDouble::from_bigfloat(&"0.131825904205311970493132056385139".parse().unwrap());
+        let (x, y) = (big(a), big(b));
+        assert!(error(a + b, &(&x + &y)).abs() < 1e-31);
+        assert!(error(a - b, &(&x - &y)).abs() < 1e-31);
+        assert!(error(a * b, &(&x * &y)).abs() < 1e-31);
+        assert!(error((a / b) * b, &x).abs() < 1e-30);
+        assert!(error(a % b, &(&x + &(&y * &BigFloat::from_f64(5.)))).abs() < 1e-30);
+        assert!(a < b && -b < a.abs());
+    }
+}
@@ -0,0 +1,330 @@
+use num_traits::{Num, One, Zero};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+use std::str::FromStr;
+
+use crate::bigfloat::{BigFloat, ParseBigFloatError};
+
+// f64 mantissa with a separate exponent: mantissa * 2^exponent
+// The mantissa is kept in [1, 2) (or 0) so the range is only bound by i64
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FloatExp {
+    mantissa: f64,
+    exponent: i64,
+}
+
+// value * 2^exponent, split so that every factor stays a normal f64
+fn ldexp(value: f64, exponent: i64) -> f64 {
+    if exponent < -2200 {
+        return 0. * value;
+    }
+    if exponent > 2200 {
+        return value * f64::INFINITY;
+    }
+    let mut value = value;
+    let mut exponent = exponent;
+    while exponent > 1000 {
+        value *= f64::from_bits(2023 << 52);
+        exponent -= 1000;
+    }
+    while exponent < -1000 {
+        value *= f64::from_bits(23 << 52);
+        exponent += 1000;
+    }
+    value * f64::from_bits(((exponent + 1023) as u64) << 52)
+}
+
+impl FloatExp {
+    pub fn new(mantissa: f64, exponent: i64) -> FloatExp {
+        if mantissa == 0. || !mantissa.is_finite() {
+            return FloatExp {
+                mantissa,
+                exponent: 0,
+            };
+        }
+        let bits = mantissa.to_bits();
+        let biased_exponent = ((bits >> 52) & 0x7ff) as i64;
+        if biased_exponent == 0 {
+            // Subnormal
+            return FloatExp::new(mantissa * ldexp(1., 64), exponent - 64);
+        }
+        FloatExp {
+            mantissa: f64::from_bits((bits & !(0x7ff << 52)) | (1023 << 52)),
+            exponent: exponent + biased_exponent - 1023,
+        }
+    }
+
+    pub fn from_f64(value: f64) -> FloatExp {
+        FloatExp::new(value, 0)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        ldexp(self.mantissa, self.exponent)
+    }
+
+    pub fn mantissa(self) -> f64 {
+        self.mantissa
+    }
+
+    pub fn exponent(self) -> i64 {
+        self.exponent
+    }
+
+    pub fn abs(self) -> FloatExp {
+        FloatExp {
+            mantissa: self.mantissa.abs(),
+            exponent: self.exponent,
+        }
+    }
+
+    pub fn sqrt(self) -> FloatExp {
+        if self.exponent % 2 == 0 {
+            FloatExp::new(self.mantissa.sqrt(), self.exponent / 2)
+        } else {
+            FloatExp::new((2. * self.mantissa).sqrt(), (self.exponent - 1) / 2)
+        }
+    }
+
+    pub fn log2(self) -> f64 {
+        self.mantissa.log2() + self.exponent as f64
+    }
+
+    pub fn log10(self) -> f64 {
+        self.log2() * std::f64::consts::LOG10_2
+    }
+
+    pub fn powi(self, n: i64) -> FloatExp {
+        if n < 0 {
+            return FloatExp::one() / self.powi(-n);
+        }
+        let mut result = FloatExp::one();
+        let mut base = self;
+        let mut n = n;
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            n >>= 1;
+        }
+        result
+    }
+
+    pub fn min(self, other: FloatExp) -> FloatExp {
+        if other < self {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn trunc(self) -> FloatExp {
+        if self.exponent >= 52 {
+            self
+        } else {
+            FloatExp::from_f64(self.to_f64().trunc())
+        }
+    }
+}
+
+impl Add for FloatExp {
+    type Output = FloatExp;
+
+    fn add(self, other: FloatExp) -> FloatExp {
+        if self.mantissa == 0. {
+            return other;
+        }
+        if other.mantissa == 0. {
+            return self;
+        }
+        let (big, small) = if self.exponent >= other.exponent {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let shift = big.exponent - small.exponent;
+        if shift > 64 {
+            return big;
+        }
+        FloatExp::new(big.mantissa + ldexp(small.mantissa, -shift), big.exponent)
+    }
+}
+
+impl Sub for FloatExp {
+    type Output = FloatExp;
+
+    fn sub(self, other: FloatExp) -> FloatExp {
+        self + -other
+    }
+}
+
+impl Mul for FloatExp {
+    type Output = FloatExp;
+
+    fn mul(self, other: FloatExp) -> FloatExp {
+        FloatExp::new(
+            self.mantissa * other.mantissa,
+            self.exponent + other.exponent,
+        )
+    }
+}
+
+impl Div for FloatExp {
+    type Output = FloatExp;
+
+    fn div(self, other: FloatExp) -> FloatExp {
+        FloatExp::new(
+            self.mantissa / other.mantissa,
+            self.exponent - other.exponent,
+        )
+    }
+}
+
+impl Rem for FloatExp {
+    type Output = FloatExp;
+
+    fn rem(self, other: FloatExp) -> FloatExp {
+        self - other * (self / other).trunc()
+    }
+}
+
+impl Neg for FloatExp {
+    type Output = FloatExp;
+
+    fn neg(self) -> FloatExp {
+        FloatExp {
+            mantissa: -self.mantissa,
+            exponent: self.exponent,
+        }
+    }
+}
+
+impl PartialOrd for FloatExp {
+    fn partial_cmp(&self, other: &FloatExp) -> Option<Ordering> {
+        let sign = self.mantissa.signum() * (self.mantissa != 0.) as i32 as f64;
+        let other_sign = other.mantissa.signum() * (other.mantissa != 0.) as i32 as f64;
+        if sign != other_sign || sign == 0. {
+            return sign.partial_cmp(&other_sign);
+        }
+        let ordering = self
+            .exponent
+            .cmp(&other.exponent)
+            .then(self.mantissa.abs().partial_cmp(&other.mantissa.abs())?);
+        Some(if sign > 0. {
+            ordering
+        } else {
+            ordering.reverse()
+        })
+    }
+}
+
+impl Zero for FloatExp {
+    fn zero() -> FloatExp {
+        FloatExp {
+            mantissa: 0.,
+            exponent: 0,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.mantissa == 0.
+    }
+}
+
+impl One for FloatExp {
+    fn one() -> FloatExp {
+        FloatExp {
+            mantissa: 1.,
+            exponent: 0,
+        }
+    }
+}
+
+impl Num for FloatExp {
+    type FromStrRadixErr = ParseBigFloatError;
+
+    fn from_str_radix(s: &str, radix: u32) -> Result<FloatExp, ParseBigFloatError> {
+        if radix != 10 {
+            return Err(ParseBigFloatError);
+        }
+        s.parse()
+    }
+}
+
+impl fmt::Display for FloatExp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.mantissa == 0. || (self.exponent > -1000 && self.exponent < 1000) {
+            return write!(f, "{:e}", self.to_f64());
+        }
+        let mut exponent = self.abs().log10().floor() as i64;
+        let mut mantissa = (*self / FloatExp::from_f64(10.).powi(exponent)).to_f64();
+        // log10 rounding can leave the mantissa just out of [1, 10)
+        if format!("{:.14}", mantissa.abs()).starts_with("10") {
+            mantissa /= 10.;
+            exponent += 1;
+        } else if mantissa.abs() < 1. {
+            mantissa *= 10.;
+            exponent -= 1;
+        }
+        let mantissa = format!("{:.14}", mantissa);
+        let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+        write!(f, "{}e{}", mantissa, exponent)
+    }
+}
+
+impl FromStr for FloatExp {
+    type Err = ParseBigFloatError;
+
+    fn from_str(s: &str) -> Result<FloatExp, ParseBigFloatError> {
+        Ok(s.parse::<BigFloat>()?.to_floatexp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mantissa_is_normalised() {
+        let value = FloatExp::from_f64(12.);
+        assert_eq!((value.mantissa(), value.exponent()), (1.5, 3));
+        let subnormal = FloatExp::from_f64(f64::MIN_POSITIVE / 4.);
+        assert_eq!((subnormal.mantissa(), subnormal.exponent()), (1., -1024));
+        assert_eq!(FloatExp::from_f64(-0.75).to_f64(), -0.75);
+    }
+
+    #[test]
+    fn arithmetic_matches_f64() {
+        let (a, b) = (3.25, -0.0625);
+        let (x, y) = (FloatExp::from_f64(a), FloatExp::from_f64(b));
+        assert_eq!((x + y).to_f64(), a + b);
+        assert_eq!((x - y).to_f64(), a - b);
+        assert_eq!((x * y).to_f64(), a * b);
+        assert_eq!((x / y).to_f64(), a / b);
+        assert_eq!((x % FloatExp::from_f64(2.)).to_f64(), 1.25);
+        assert_eq!(FloatExp::from_f64(2.25).sqrt().to_f64(), 1.5);
+        assert_eq!(FloatExp::from_f64(2.).powi(-3).to_f64(), 0.125);
+        assert!(y < x && -x < y && FloatExp::zero() < x);
+    }
+
+    #[test]
+    fn exponents_go_beyond_f64() {
+        let tiny = FloatExp::from_f64(1e-300).powi(10);
+        assert_eq!(tiny.to_f64(), 0.);
+        assert!((tiny.log10() + 3000.).abs() < 1e-9);
+        assert!(((tiny * FloatExp::from_f64(1e300).powi(10)).to_f64() - 1.).abs() < 1e-12);
+        assert!(FloatExp::zero() < tiny && tiny < FloatExp::from_f64(f64::MIN_POSITIVE));
+        assert!(((tiny.sqrt().powi(2) / tiny).to_f64() - 1.).abs() < 1e-14);
+        assert_eq!(tiny.to_string().split('e').nth(1), Some("-3000"));
+        assert_eq!(
+            "1e-400"
+                .parse::<FloatExp>()
+                .unwrap()
+                .to_string()
+                .split('e')
+                .nth(1),
+            Some("-400")
+        );
+    }
+}
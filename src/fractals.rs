@@ -1,9 +1,17 @@
 use color_processing::Color;
 use wasm_bindgen::prelude::*;
 
+use crate::colorspace::ColorSpace;
+use crate::histogram::Histogram;
+use crate::kernel::Arithmetic;
+use crate::layers::Layer;
+use crate::palette::Palette;
 use crate::pixel::Pixel;
-use crate::point::Point;
-use num_complex::Complex64 as Complex;
+use crate::scalar::Scalar;
+use crate::strategy::Strategy;
+use num_complex::{Complex, Complex64};
+use num_traits::Zero;
+use std::f64::consts::PI;
 
 #[wasm_bindgen]
 #[derive(Clone, Copy, PartialEq)]
@@ -21,8 +29,16 @@ pub enum Variant {
     Newton7,
 }
 
+fn complex<T: Scalar>(re: f64, im: f64) -> Complex<T> {
+    Complex::new(T::from_f64(re), T::from_f64(im))
+}
+
+fn complex64<T: Scalar>(z: Complex<T>) -> Complex64 {
+    Complex64::new(z.re.to_f64(), z.im.to_f64())
+}
+
 impl Variant {
-    pub fn get_fractal(&self) -> Box<dyn Fractal> {
+    pub fn get_fractal<T: Scalar + 'static>(&self) -> Box<dyn Fractal<T>> {
         match *self {
             // zn+1 = zn^d + c
             Variant::Mandelbrot => Box::new(Mandelbrot {}),
@@ -36,113 +52,120 @@ impl Variant {
             // zn+1 = zn - p(zn) / p'(zn)
             // p = z³ - 1
             Variant::Newton => Box::new(Newton {
-                polynomial: |z| z.powi(3) - Complex::new(1., 0.),
-                derivative: |z| Complex::new(3., 0.) * z.powi(2),
+                polynomial: |z| z.powi(3) - complex::<T>(1., 0.),
+                derivative: |z| complex::<T>(3., 0.) * z.powi(2),
                 roots: vec![
-                    Complex::new(1., 0.),
-                    Complex::new(-0.5, (3_f64).sqrt() / 2_f64),
-                    Complex::new(-0.5, -(3_f64).sqrt() / 2_f64),
+                    complex::<T>(1., 0.),
+                    complex::<T>(-0.5, 3_f64.sqrt() / 2_f64),
+                    complex::<T>(-0.5, -3_f64.sqrt() / 2_f64),
                 ],
             }),
             // p = z³ - 2z + 2
             Variant::Newton2 => Box::new(Newton {
-                polynomial: |z| z.powi(3) - Complex::new(2., 0.) * z + Complex::new(2., 0.),
-                derivative: |z| Complex::new(3., 0.) * z.powi(2) - Complex::new(4., 0.),
+                polynomial: |z| z.powi(3) - complex::<T>(2., 0.) * z + complex::<T>(2., 0.),
+                derivative: |z| complex::<T>(3., 0.) * z.powi(2) - complex::<T>(4., 0.),
                 roots: vec![
-                    Complex::new(-1.7693, 0.),
-                    Complex::new(0.88465, -0.58974),
-                    Complex::new(0.88465, 0.58974),
+                    complex::<T>(-1.7693, 0.),
+                    complex::<T>(0.88465, -0.58974),
+                    complex::<T>(0.88465, 0.58974),
                 ],
             }),
             // p = z⁶ + z³ - 1
             Variant::Newton3 => Box::new(Newton {
-                polynomial: |z| z.powi(6) + z.powi(3) - Complex::new(1., 0.),
-                derivative: |z| Complex::new(6., 0.) * z.powi(5) + Complex::new(3., 0.) * z.powi(2),
+                polynomial: |z| z.powi(6) + z.powi(3) - complex::<T>(1., 0.),
+                derivative: |z| complex::<T>(6., 0.) * z.powi(5) + complex::<T>(3., 0.) * z.powi(2),
                 roots: vec![
-                    Complex::new(0.58699, 1.01670),
-                    Complex::new(0.85180, 0.0),
-                    Complex::new(0.58699, -1.01670),
-                    Complex::new(-0.42590, -0.73768),
-                    Complex::new(-1.1740, 0.0),
-                    Complex::new(-0.42590, 0.73768),
+                    complex::<T>(0.58699, 1.01670),
+                    complex::<T>(0.85180, 0.0),
+                    complex::<T>(0.58699, -1.01670),
+                    complex::<T>(-0.42590, -0.73768),
+                    complex::<T>(-1.1740, 0.0),
+                    complex::<T>(-0.42590, 0.73768),
                 ],
             }),
             // p = z⁵ - 2
             Variant::Newton4 => Box::new(Newton {
-                polynomial: |z| z.powi(5) - Complex::new(2., 0.),
-                derivative: |z| Complex::new(5., 0.) * z.powi(4),
+                polynomial: |z| z.powi(5) - complex::<T>(2., 0.),
+                derivative: |z| complex::<T>(5., 0.) * z.powi(4),
                 roots: vec![
-                    Complex::new(-0.929316, -0.675188),
-                    Complex::new(-0.929316, 0.675188),
-                    Complex::new(0.354967, -1.09248),
-                    Complex::new(0.354967, 1.09248),
-                    Complex::new(1.1487, 0.),
+                    complex::<T>(-0.929316, -0.675188),
+                    complex::<T>(-0.929316, 0.675188),
+                    complex::<T>(0.354967, -1.09248),
+                    complex::<T>(0.354967, 1.09248),
+                    complex::<T>(1.1487, 0.),
                 ],
             }),
             // p = z³ - 1 + 1/z
             Variant::Newton5 => Box::new(Newton {
-                polynomial: |z| z.powi(3) - Complex::new(1., 0.) + 1. / z,
+                polynomial: |z| z.powi(3) - complex::<T>(1., 0.) + z.inv(),
                 derivative: |z| {
-                    (Complex::new(3., 0.) * z.powi(4) - Complex::new(1., 0.)) / z.powi(2)
+                    (complex::<T>(3., 0.) * z.powi(4) - complex::<T>(1., 0.)) / z.powi(2)
                 },
                 roots: vec![
-                    Complex::new(-0.72714, -0.93410),
-                    Complex::new(-0.72714, 0.93410),
-                    Complex::new(0.72714, -0.43001),
-                    Complex::new(0.72714, 0.43001),
+                    complex::<T>(-0.72714, -0.93410),
+                    complex::<T>(-0.72714, 0.93410),
+                    complex::<T>(0.72714, -0.43001),
+                    complex::<T>(0.72714, 0.43001),
                 ],
             }),
             // p = z⁹ - 1
             Variant::Newton6 => Box::new(Newton {
-                polynomial: |z| z.powi(9) - Complex::new(1., 0.),
-                derivative: |z| Complex::new(9., 0.) * z.powi(8),
+                polynomial: |z| z.powi(9) - complex::<T>(1., 0.),
+                derivative: |z| complex::<T>(9., 0.) * z.powi(8),
                 roots: vec![
-                    Complex::new(1., 0.),
-                    Complex::new(-0.93969, -0.34202),
-                    Complex::new(0.76604, 0.64279),
-                    Complex::new(-0.50000, -0.86603),
-                    Complex::new(0.17365, 0.98481),
-                    Complex::new(0.17365, -0.98481),
-                    Complex::new(-0.50000, 0.86603),
-                    Complex::new(0.76604, -0.64279),
-                    Complex::new(-0.93969, 0.34202),
+                    complex::<T>(1., 0.),
+                    complex::<T>(-0.93969, -0.34202),
+                    complex::<T>(0.76604, 0.64279),
+                    complex::<T>(-0.50000, -0.86603),
+                    complex::<T>(0.17365, 0.98481),
+                    complex::<T>(0.17365, -0.98481),
+                    complex::<T>(-0.50000, 0.86603),
+                    complex::<T>(0.76604, -0.64279),
+                    complex::<T>(-0.93969, 0.34202),
                 ],
             }),
             // p = 1/z⁶ + 8 / z³ - 1
             Variant::Newton7 => Box::new(Newton {
                 polynomial: |z| {
-                    z.powi(-6) + Complex::new(8., 0.) * z.powi(-3) - Complex::new(1., 0.)
+                    z.powi(-6) + complex::<T>(8., 0.) * z.powi(-3) - complex::<T>(1., 0.)
                 },
                 derivative: |z| {
-                    -(Complex::new(6., 0.)
-                        * (Complex::new(4., 0.) * z.powi(3) + Complex::new(1., 0.))
-                        * z.powi(-7))
+                    complex::<T>(-6., 0.)
+                        * (complex::<T>(4., 0.) * z.powi(3) + complex::<T>(1., 0.))
+                        * z.powi(-7)
                 },
                 roots: vec![
-                    Complex::new(-1.0051, -1.7409),
-                    Complex::new(0.24873, 0.43081),
-                    Complex::new(0.24873, -0.43081),
-                    Complex::new(-0.49746, 0.),
-                    Complex::new(2.0102, 0.),
-                    Complex::new(-1.0051, 1.7409),
+                    complex::<T>(-1.0051, -1.7409),
+                    complex::<T>(0.24873, 0.43081),
+                    complex::<T>(0.24873, -0.43081),
+                    complex::<T>(-0.49746, 0.),
+                    complex::<T>(2.0102, 0.),
+                    complex::<T>(-1.0051, 1.7409),
                 ],
             }),
         }
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct Iterations {
-    n: f64,
-    channel: usize,
-    channels: usize,
+    pub n: f64,
+    pub channel: usize,
+    pub channels: usize,
+    // Average coloring value in [0, 1] when one is enabled
+    pub average: Option<f64>,
+    // Final distance to the root for Newton fractals
+    pub distance: Option<f64>,
 }
 
 impl Iterations {
-    fn all(n: f64) -> Iterations {
+    pub fn all(n: f64) -> Iterations {
         Iterations {
             n,
             channel: 0,
             channels: 1,
+            average: None,
+            distance: None,
         }
     }
 }
@@ -154,37 +177,218 @@ pub type IterationsMaybe = Option<Iterations>;
 pub enum Colorization {
     Relative,
     Absolute,
+    Histogram,
+}
+
+// Colorings averaging a value along the escaping orbit
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Average {
+    None,
+    Stripe,
+    TriangleInequality,
+    Curvature,
+}
+
+// How Newton roots colours get darker
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum RootShading {
+    Iterations,
+    Distance,
+    Both,
 }
 
 #[wasm_bindgen]
 #[derive(Clone, Copy, PartialEq)]
 pub struct Options {
     pub precision: usize,
+    pub arithmetic: Arithmetic,
+    pub strategy: Strategy,
     pub smooth: bool,
     pub variant: Variant, // for gui purpose
     pub order: i32,
     pub const_real: f64,
     pub const_imaginary: f64,
+    pub average: Average,
+    pub stripe_density: f64,
     pub colorization: Colorization,
     pub black_threshold: f64,
     pub white_threshold: f64,
     pub overexposure: f64,
     pub color_rotation: f64,
     pub color_base: f64,
+    pub color_space: ColorSpace,
+    pub root_shading: RootShading,
+    pub basin_boundary: f64,
+    pub lighting: bool,
+    pub light_azimuth: f64,
+    pub light_elevation: f64,
+    pub light_height: f64,
+    pub ambient: f64,
+    pub diffuse: f64,
+    pub specular: f64,
+    pub shininess: f64,
+}
+
+impl Options {
+    // Whether both options give the same iterations, colouring aside
+    pub fn same_iterations(&self, other: &Options) -> bool {
+        self.precision == other.precision
+            && self.arithmetic == other.arithmetic
+            && self.strategy == other.strategy
+            && self.smooth == other.smooth
+            && self.variant == other.variant
+            && self.order == other.order
+            && self.const_real == other.const_real
+            && self.const_imaginary == other.const_imaginary
+            && self.average == other.average
+            && self.stripe_density == other.stripe_density
+    }
+}
+
+// Colour of a Newton root, by default roots are spread around the hue
+// circle
+#[derive(Clone)]
+pub enum RootColor {
+    Hue,
+    Color([f64; 3]),
+    Palette(Palette),
+}
+
+// Frame wide colouring state
+#[derive(Clone, Copy, Default)]
+pub struct Coloring<'a> {
+    pub palette: Option<&'a Palette>,
+    pub histogram: Option<&'a Histogram>,
+    pub roots: &'a [RootColor],
+    pub layers: &'a [Layer],
+}
+
+const NEWTON_EPSILON: f64 = 0.00001;
+
+// 0 when the root has just been reached, going to 1 as the final distance
+// gets smaller
+fn closeness(distance: f64) -> f64 {
+    (1. - NEWTON_EPSILON.ln() / (distance * distance).ln()).clamp(0., 1.)
+}
+
+// Smoothing is:
+// ln( ln |zn| / ln B ) / ln d
+// where B is max(|c|;2^(1/d-1)) and d is the order
+pub fn escape_smoothing(mod2: f64, c_norm: f64, order: i32) -> f64 {
+    ((mod2.ln() / 2.) / c_norm.max((2.0f64).powf(1. / (order as f64 - 1.)))).ln()
+        / (order as f64).ln()
+}
+
+// Julia smoothing, B being unknown we use an empirical factor
+pub fn julia_smoothing(mod2: f64) -> f64 {
+    mod2.ln().ln() * 1.25
+}
+
+// Averages need orbits going well past |z| = 2 to look smooth
+const AVERAGE_BAILOUT: f64 = 1e6;
+
+pub fn bailout(options: &Options) -> f64 {
+    if options.average == Average::None {
+        4.
+    } else {
+        AVERAGE_BAILOUT
+    }
+}
+
+// Running sum of the average coloring terms along an orbit
+pub struct Accumulator {
+    average: Average,
+    stripe_density: f64,
+    c: Complex64,
+    sum: f64,
+    previous_sum: f64,
+    count: usize,
+    // zn-1 and zn-2
+    last: [Complex64; 2],
 }
 
-pub trait Fractal {
-    fn get_iterations_at_point(&self, point: Point, options: &Options) -> IterationsMaybe;
+impl Accumulator {
+    pub fn new(options: &Options, c: Complex64) -> Accumulator {
+        Accumulator {
+            average: options.average,
+            stripe_density: options.stripe_density,
+            c,
+            sum: 0.,
+            previous_sum: 0.,
+            count: 0,
+            last: [Complex64::new(f64::NAN, 0.); 2],
+        }
+    }
+
+    // Called with every new zn
+    pub fn add<T: Scalar>(&mut self, z: Complex<T>) {
+        if self.average == Average::None {
+            return;
+        }
+        let z = complex64(z);
+        let term = match self.average {
+            Average::None => return,
+            Average::Stripe => 0.5 + 0.5 * (self.stripe_density * z.arg()).sin(),
+            // Where |zn| lies between the bounds given by |zn-1^d| and |c|
+            Average::TriangleInequality => {
+                let power = (z - self.c).norm();
+                let low = (power - self.c.norm()).abs();
+                (z.norm() - low) / (power + self.c.norm() - low)
+            }
+            Average::Curvature => {
+                ((z - self.last[0]) / (self.last[0] - self.last[1]))
+                    .arg()
+                    .abs()
+                    / PI
+            }
+        };
+        self.last = [z, self.last[0]];
+        if term.is_finite() {
+            self.previous_sum = self.sum;
+            self.sum += term;
+            self.count += 1;
+        }
+    }
+
+    // The last two averages are interpolated with the fractional part of
+    // the smooth iteration count to avoid banding
+    pub fn finish(&self, mod2: f64, options: &Options) -> Option<f64> {
+        if self.average == Average::None || self.count == 0 {
+            return None;
+        }
+        let last = self.sum / self.count as f64;
+        if self.count == 1 || !options.smooth {
+            return Some(last);
+        }
+        let previous = self.previous_sum / (self.count - 1) as f64;
+        let fraction =
+            ((mod2.ln() / AVERAGE_BAILOUT.ln()).ln() / f64::from(options.order).ln()).clamp(0., 1.);
+        Some(last + (previous - last) * fraction)
+    }
+}
 
-    fn get_pixel_for_iteration(&self, iterations: IterationsMaybe, options: &Options) -> Pixel {
+pub trait Fractal<T: Scalar>: Send + Sync {
+    fn get_iterations_at_point(&self, point: Complex<T>, options: &Options) -> IterationsMaybe;
+
+    // Colour either comes from the hwb hue rotation or from the palette when
+    // one is given, whiteness and blackness apply to both
+    fn get_pixel_for_iteration(
+        &self,
+        iterations: IterationsMaybe,
+        options: &Options,
+        coloring: &Coloring,
+    ) -> Pixel {
         if let Some(Iterations {
             n,
             channel,
             channels,
+            average,
+            distance,
         }) = iterations
         {
-            let channel_hue = options.color_base + channel as f64 * 360. / channels as f64;
-            match options.colorization {
+            let (progress, w, b) = match options.colorization {
                 Colorization::Relative => {
                     let (black_threshold, white_threshold) =
                         if options.black_threshold <= options.white_threshold {
@@ -211,17 +415,15 @@ pub trait Fractal {
                         0.
                     };
 
-                    let h = channel_hue
-                        + if convergence >= black_threshold && convergence <= white_threshold {
-                            options.color_rotation * (convergence - black_threshold)
-                                / (white_threshold - black_threshold)
+                    let progress =
+                        if convergence >= black_threshold && convergence <= white_threshold {
+                            (convergence - black_threshold) / (white_threshold - black_threshold)
                         } else if convergence > white_threshold {
-                            options.color_rotation
+                            1.
                         } else {
                             0.
                         };
-
-                    Pixel::from_color(Color::new_hwb(h, w, b))
+                    (progress, w, b)
                 }
                 Colorization::Absolute => {
                     let (black_threshold, white_threshold) =
@@ -241,16 +443,49 @@ pub trait Fractal {
                         0.
                     };
 
-                    let h = channel_hue
-                        + if convergence >= black_threshold && convergence <= white_threshold {
-                            options.color_rotation * (convergence - black_threshold)
-                        } else if convergence > white_threshold {
-                            options.color_rotation * (convergence - black_threshold)
-                        } else {
-                            0.
-                        };
-
-                    Pixel::from_color(Color::new_hwb(h, w, b))
+                    let progress = if convergence >= black_threshold {
+                        convergence - black_threshold
+                    } else {
+                        0.
+                    };
+                    (progress, w, b)
+                }
+                // Equalised by the frame histogram, no thresholds needed
+                Colorization::Histogram => (
+                    coloring
+                        .histogram
+                        .map_or(0., |histogram| histogram.fraction(n)),
+                    0.,
+                    0.,
+                ),
+            };
+
+            let (w, b) = match (distance.map(closeness), options.root_shading) {
+                (Some(closeness), RootShading::Distance) => (0., 1. - closeness),
+                (Some(closeness), RootShading::Both) => (w, 1. - (1. - b) * closeness),
+                _ => (w, b),
+            };
+
+            // Averages replace the iteration based hue or palette position
+            let progress = average.unwrap_or(progress);
+            let root = channel
+                .checked_sub(1)
+                .and_then(|root| coloring.roots.get(root));
+            match (root, coloring.palette) {
+                (Some(RootColor::Color(color)), _) => Pixel::from_rgb_mixed(*color, w, b),
+                (Some(RootColor::Palette(palette)), _) => palette.get_pixel(progress, 0., w, b),
+                (_, Some(palette)) => {
+                    palette.get_pixel(progress, channel as f64 / channels as f64, w, b)
+                }
+                (_, None) => {
+                    let h = options.color_base
+                        + channel as f64 * 360. / channels as f64
+                        + options.color_rotation * progress;
+                    if options.color_space == ColorSpace::Hwb {
+                        Pixel::from_color(Color::new_hwb(h, w, b))
+                    } else {
+                        Pixel::from_rgb(options.color_space.hue(h, w, b))
+                    }
                 }
             }
         } else {
@@ -258,26 +493,42 @@ pub trait Fractal {
         }
     }
 
-    fn get_pixel_at_point(&self, point: Point, options: &Options) -> Pixel {
-        self.get_pixel_for_iteration(self.get_iterations_at_point(point, options), options)
+    // Number of colour channels, the roots of Newton fractals
+    fn channels(&self) -> usize {
+        1
+    }
+
+    fn get_pixel_at_point(
+        &self,
+        point: Complex<T>,
+        options: &Options,
+        coloring: &Coloring,
+    ) -> Pixel {
+        self.get_pixel_for_iteration(
+            self.get_iterations_at_point(point, options),
+            options,
+            coloring,
+        )
     }
 }
 
 pub struct Mandelbrot {}
-impl Fractal for Mandelbrot {
-    fn get_iterations_at_point(&self, point: Point, options: &Options) -> IterationsMaybe {
-        let mut z = Complex::new(0_f64, 0_f64);
-        let c = Complex::new(point.x, point.y);
+impl<T: Scalar> Fractal<T> for Mandelbrot {
+    fn get_iterations_at_point(&self, point: Complex<T>, options: &Options) -> IterationsMaybe {
+        let mut z: Complex<T> = Complex::zero();
+        let c = point;
 
         if options.order == 2 {
-            let p = ((point.x - 1. / 4.).powi(2) + point.y.powi(2)).sqrt();
-            if (point.x < p - 2. * p.powi(2) + 1. / 4.)
-                || ((point.x + 1.).powi(2) + point.y.powi(2) < 1. / 16.)
-            {
+            // Cardioid and period 2 bulb checks don't need full precision
+            let (x, y) = (point.re.to_f64(), point.im.to_f64());
+            let p = ((x - 1. / 4.).powi(2) + y.powi(2)).sqrt();
+            if (x < p - 2. * p.powi(2) + 1. / 4.) || ((x + 1.).powi(2) + y.powi(2) < 1. / 16.) {
                 return None;
             }
         }
 
+        let bailout = T::from_f64(bailout(options));
+        let mut accumulator = Accumulator::new(options, complex64(c));
         let mut iterations = 0;
         while iterations < options.precision {
             // zn+1 = zn^d + c
@@ -289,20 +540,21 @@ impl Fractal for Mandelbrot {
             // |z| = sqrt(a² + b²)
             // |z|² = a² + b² =
             let mod2 = z.norm_sqr();
+            accumulator.add(z);
             // |z| > 2 => |z|² > 4
-            if mod2 > 4. {
+            if mod2 > bailout {
                 let mut n = iterations as f64;
                 if options.smooth {
-                    // Smoothing is:
-                    // ln( ln |zn| / ln B ) / ln d
-                    // where B is max(|c|;2^(1/d-1)) and d is the order
-                    n -= ((mod2.ln() / 2.)
-                        / c.norm()
-                            .max((2.0f64).powf(1. / (options.order as f64 - 1.))))
-                    .ln()
-                        / (options.order as f64).ln();
+                    n -= escape_smoothing(
+                        mod2.to_f64(),
+                        c.norm_sqr().to_f64().sqrt(),
+                        options.order,
+                    );
                 }
-                return Some(Iterations::all(n));
+                return Some(Iterations {
+                    average: accumulator.finish(mod2.to_f64(), options),
+                    ..Iterations::all(n)
+                });
             }
 
             iterations += 1;
@@ -311,37 +563,40 @@ impl Fractal for Mandelbrot {
     }
 }
 
-pub struct Newton {
-    polynomial: fn(Complex) -> Complex,
-    derivative: fn(Complex) -> Complex,
-    roots: Vec<Complex>,
+pub struct Newton<T> {
+    polynomial: fn(Complex<T>) -> Complex<T>,
+    derivative: fn(Complex<T>) -> Complex<T>,
+    roots: Vec<Complex<T>>,
 }
 
-impl Fractal for Newton {
-    fn get_iterations_at_point(&self, point: Point, options: &Options) -> IterationsMaybe {
-        let mut z = Complex::new(point.x, point.y);
-        let c = Complex::new(options.const_real, options.const_imaginary);
+impl<T: Scalar> Fractal<T> for Newton<T> {
+    fn get_iterations_at_point(&self, point: Complex<T>, options: &Options) -> IterationsMaybe {
+        let mut z = point;
+        let c: Complex<T> = complex(options.const_real, options.const_imaginary);
 
         let mut iterations = 0;
-        let epsilon = 0.00001_f64;
+        let epsilon = NEWTON_EPSILON;
+        let epsilon_t = T::from_f64(epsilon);
 
         let mut last_z;
         while iterations < options.precision {
             last_z = z;
-            z -= c * (self.polynomial)(z) / (self.derivative)(z);
-            for (i, root) in self.roots.iter().enumerate() {
+            z = z - c * (self.polynomial)(z) / (self.derivative)(z);
+            for (i, &root) in self.roots.iter().enumerate() {
                 let convergence = (z - root).norm_sqr();
-                if convergence < epsilon {
+                if convergence < epsilon_t {
                     let mut n = iterations as f64;
                     if options.smooth {
-                        let prev_ln_convergence = (last_z - root).norm_sqr().ln();
+                        let prev_ln_convergence = (last_z - root).norm_sqr().to_f64().ln();
                         n += (epsilon.ln() - prev_ln_convergence)
-                            / (convergence.ln() - prev_ln_convergence);
+                            / (convergence.to_f64().ln() - prev_ln_convergence);
                     }
                     return Some(Iterations {
                         n,
                         channel: i + 1,
                         channels: self.roots.len(),
+                        average: None,
+                        distance: Some(convergence.to_f64().sqrt()),
                     });
                 }
             }
@@ -349,26 +604,36 @@ impl Fractal for Newton {
         }
         None
     }
+
+    fn channels(&self) -> usize {
+        self.roots.len()
+    }
 }
 
 pub struct Julia {}
-impl Fractal for Julia {
-    fn get_iterations_at_point(&self, point: Point, options: &Options) -> IterationsMaybe {
-        let mut z = Complex::new(point.x, point.y);
-        let c = Complex::new(options.const_real, options.const_imaginary);
+impl<T: Scalar> Fractal<T> for Julia {
+    fn get_iterations_at_point(&self, point: Complex<T>, options: &Options) -> IterationsMaybe {
+        let mut z = point;
+        let c: Complex<T> = complex(options.const_real, options.const_imaginary);
+        let bailout = T::from_f64(bailout(options));
+        let mut accumulator = Accumulator::new(options, complex64(c));
         let mut iterations = 0;
 
         while iterations < options.precision {
             // zn+1 = zn² + c
             z = z.powi(options.order) + c;
             let mod2 = z.norm_sqr();
-            if mod2 > 4. {
+            accumulator.add(z);
+            if mod2 > bailout {
                 let mut n = iterations as f64;
                 if options.smooth {
-                    n -= mod2.ln().ln() * 1.25;
+                    n -= julia_smoothing(mod2.to_f64());
                 }
 
-                return Some(Iterations::all(n));
+                return Some(Iterations {
+                    average: accumulator.finish(mod2.to_f64(), options),
+                    ..Iterations::all(n)
+                });
             }
             iterations += 1;
         }
@@ -377,11 +642,13 @@ impl Fractal for Julia {
 }
 
 pub struct Mandelbar {}
-impl Fractal for Mandelbar {
-    fn get_iterations_at_point(&self, point: Point, options: &Options) -> IterationsMaybe {
-        let mut z = Complex::new(0_f64, 0_f64);
-        let c = Complex::new(point.x, point.y);
+impl<T: Scalar> Fractal<T> for Mandelbar {
+    fn get_iterations_at_point(&self, point: Complex<T>, options: &Options) -> IterationsMaybe {
+        let mut z: Complex<T> = Complex::zero();
+        let c = point;
 
+        let bailout = T::from_f64(bailout(options));
+        let mut accumulator = Accumulator::new(options, complex64(c));
         let mut iterations = 0;
         while iterations < options.precision {
             // zn+1 = conj(zn)^d + c
@@ -390,20 +657,21 @@ impl Fractal for Mandelbar {
             // |z| = sqrt(a² + b²)
             // |z|² = a² + b² =
             let mod2 = z.norm_sqr();
+            accumulator.add(z);
             // |z| > 2 => |z|² > 4
-            if mod2 > 4. {
+            if mod2 > bailout {
                 let mut n = iterations as f64;
                 if options.smooth {
-                    // Smoothing is:
-                    // ln( ln |zn| / ln B ) / ln d
-                    // where B is max(|c|;2^(1/d-1)) and d is the order
-                    n -= ((mod2.ln() / 2.)
-                        / c.norm()
-                            .max((2.0f64).powf(1. / (options.order as f64 - 1.))))
-                    .ln()
-                        / (options.order as f64).ln();
+                    n -= escape_smoothing(
+                        mod2.to_f64(),
+                        c.norm_sqr().to_f64().sqrt(),
+                        options.order,
+                    );
                 }
-                return Some(Iterations::all(n));
+                return Some(Iterations {
+                    average: accumulator.finish(mod2.to_f64(), options),
+                    ..Iterations::all(n)
+                });
             }
 
             iterations += 1;
@@ -413,11 +681,13 @@ impl Fractal for Mandelbar {
 }
 
 pub struct BurningShip {}
-impl Fractal for BurningShip {
-    fn get_iterations_at_point(&self, point: Point, options: &Options) -> IterationsMaybe {
-        let mut z = Complex::new(0_f64, 0_f64);
-        let c = Complex::new(point.x, point.y);
+impl<T: Scalar> Fractal<T> for BurningShip {
+    fn get_iterations_at_point(&self, point: Complex<T>, options: &Options) -> IterationsMaybe {
+        let mut z: Complex<T> = Complex::zero();
+        let c = point;
 
+        let bailout = T::from_f64(bailout(options));
+        let mut accumulator = Accumulator::new(options, complex64(c));
         let mut iterations = 0;
         while iterations < options.precision {
             // zn+1 = (abs(Re(zn)) + abs(Im(zn)))² + c
@@ -427,20 +697,21 @@ impl Fractal for BurningShip {
             // |z| = sqrt(a² + b²)
             // |z|² = a² + b²
             let mod2 = z.norm_sqr();
+            accumulator.add(z);
             // |z| > 2 => |z|² > 4
-            if mod2 > 4. {
+            if mod2 > bailout {
                 let mut n = iterations as f64;
                 if options.smooth {
-                    // Smoothing is:
-                    // ln( ln |zn| / ln B ) / ln d
-                    // where B is max(|c|;2^(1/d-1)) and d is the order
-                    n -= ((mod2.ln() / 2.)
-                        / c.norm()
-                            .max((2.0f64).powf(1. / (options.order as f64 - 1.))))
-                    .ln()
-                        / (options.order as f64).ln();
+                    n -= escape_smoothing(
+                        mod2.to_f64(),
+                        c.norm_sqr().to_f64().sqrt(),
+                        options.order,
+                    );
                 }
-                return Some(Iterations::all(n));
+                return Some(Iterations {
+                    average: accumulator.finish(mod2.to_f64(), options),
+                    ..Iterations::all(n)
+                });
             }
 
             iterations += 1;
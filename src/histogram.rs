@@ -0,0 +1,54 @@
+use crate::fractals::Iterations;
+
+// Cumulative distribution of the iteration counts of a frame
+pub struct Histogram {
+    cdf: Vec<f64>,
+}
+
+fn bin(n: f64, precision: usize) -> usize {
+    (n.max(0.) as usize).min(precision)
+}
+
+impl Histogram {
+    pub fn new<'a>(
+        iterations: impl Iterator<Item = &'a Iterations>,
+        precision: usize,
+    ) -> Histogram {
+        let mut counts = vec![0usize; precision + 1];
+        Histogram::count(&mut counts, iterations);
+        Histogram::from_counts(&counts)
+    }
+
+    // Adds iterations to the counts of each whole number of iterations up to
+    // the precision, the last count
+    pub fn count<'a>(counts: &mut [usize], iterations: impl Iterator<Item = &'a Iterations>) {
+        let precision = counts.len() - 1;
+        for iterations in iterations {
+            counts[bin(iterations.n, precision)] += 1;
+        }
+    }
+
+    pub fn from_counts(counts: &[usize]) -> Histogram {
+        let total = counts.iter().sum::<usize>().max(1) as f64;
+        let mut cumulative = 0;
+        Histogram {
+            cdf: counts
+                .iter()
+                .map(|count| {
+                    cumulative += count;
+                    cumulative as f64 / total
+                })
+                .collect(),
+        }
+    }
+
+    // Fraction of the frame that escaped before n, the smooth part of n
+    // interpolates inside its bin
+    pub fn fraction(&self, n: f64) -> f64 {
+        let precision = self.cdf.len() - 1;
+        let bin = bin(n, precision);
+        let low = if bin == 0 { 0. } else { self.cdf[bin - 1] };
+        let part = (n.max(0.) - bin as f64).min(1.);
+        low + (self.cdf[bin] - low) * part
+    }
+}
@@ -0,0 +1,203 @@
+use num_complex::Complex;
+use wasm_bindgen::prelude::*;
+
+use crate::batch::{self, LANES};
+use crate::domain::Domain;
+use crate::doubledouble::DoubleDouble;
+use crate::floatexp::FloatExp;
+use crate::fractals::{Fractal, IterationsMaybe, Options};
+use crate::perturbation::Perturbation;
+use crate::point::Point;
+use crate::scalar::Scalar;
+
+// Below this scale f64 can't tell neighbour pixels apart anymore
+pub const PERTURBATION_SCALE: f64 = 1e-13;
+// Minimum number of f32 ulps a pixel must span to render in f32, for each
+// iteration as rounding errors add up along the orbit
+const SINGLE_ULPS: f64 = 8.;
+// Below this scale f64 deltas underflow
+pub const FLOATEXP_SCALE: f64 = 1e-290;
+
+// Plain iteration of every pixel in the T arithmetic
+pub struct Direct<T: Scalar> {
+    fractal: Box<dyn Fractal<T>>,
+    center: Complex<T>,
+    scale: T,
+}
+
+impl<T: Scalar + 'static> Direct<T> {
+    fn new(domain: &Domain, options: &Options) -> Direct<T> {
+        Direct {
+            fractal: options.variant.get_fractal(),
+            center: Complex::new(
+                T::from_bigfloat(&domain.origin.x),
+                T::from_bigfloat(&domain.origin.y),
+            ),
+            scale: T::from_floatexp(domain.scale),
+        }
+    }
+
+    fn get_iterations_at(&self, position: Point, options: &Options) -> IterationsMaybe {
+        let offset = Complex::new(
+            T::from_f64(position.x) * self.scale,
+            T::from_f64(position.y) * self.scale,
+        );
+        self.fractal
+            .get_iterations_at_point(self.center + offset, options)
+    }
+
+    // Pixels of the row are iterated LANES at a time, the last batch being
+    // padded with its last point
+    fn get_batched_iterations(
+        &self,
+        positions: &[Point],
+        row: &mut [Option<IterationsMaybe>],
+        options: &Options,
+    ) {
+        for (positions, results) in positions.chunks(LANES).zip(row.chunks_mut(LANES)) {
+            let mut points = [self.center; LANES];
+            for (lane, point) in points.iter_mut().enumerate() {
+                let position = positions[lane.min(positions.len() - 1)];
+                *point = self.center
+                    + Complex::new(
+                        T::from_f64(position.x) * self.scale,
+                        T::from_f64(position.y) * self.scale,
+                    );
+            }
+            for (result, iterations) in results
+                .iter_mut()
+                .zip(&batch::get_iterations(points, options))
+            {
+                *result = Some(*iterations);
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Arithmetic {
+    Auto,
+    Single,
+    Double,
+    DoubleDouble,
+}
+
+// Computes iterations for pixel positions with the arithmetic fitting the
+// current scale
+pub enum Kernel<'a> {
+    Single(Direct<f32>),
+    Double(Direct<f64>),
+    DoubleDouble(Direct<DoubleDouble>),
+    Perturbation {
+        perturbation: &'a Perturbation,
+        scale: f64,
+    },
+    FloatExpPerturbation {
+        perturbation: &'a Perturbation,
+        scale: FloatExp,
+    },
+}
+
+pub fn needs_perturbation(domain: &Domain, options: &Options) -> bool {
+    options.arithmetic == Arithmetic::Auto && domain.scale < FloatExp::from_f64(PERTURBATION_SCALE)
+}
+
+// f32 is fine as long as neighbour pixels stay apart once the error of
+// every iteration is added
+fn fits_single(domain: &Domain, options: &Options) -> bool {
+    let center = domain.center();
+    let magnitude = center.x.abs().max(center.y.abs()).max(1.);
+    let pixel = 2. * domain.scale.to_f64() / domain.size.x.min(domain.size.y);
+    let error = f64::from(f32::EPSILON) * magnitude * options.precision.max(1) as f64;
+    pixel > SINGLE_ULPS * error
+}
+
+impl<'a> Kernel<'a> {
+    // Perturbation is preferred when available as it is much faster than
+    // double-double, which only reaches about 1e-28
+    pub fn new(
+        perturbation: Option<&'a Perturbation>,
+        domain: &Domain,
+        options: &Options,
+    ) -> Kernel<'a> {
+        match options.arithmetic {
+            Arithmetic::Single => return Kernel::Single(Direct::new(domain, options)),
+            Arithmetic::Double => return Kernel::Double(Direct::new(domain, options)),
+            Arithmetic::DoubleDouble => return Kernel::DoubleDouble(Direct::new(domain, options)),
+            Arithmetic::Auto => (),
+        }
+        if fits_single(domain, options) {
+            return Kernel::Single(Direct::new(domain, options));
+        }
+        if !needs_perturbation(domain, options) {
+            return Kernel::Double(Direct::new(domain, options));
+        }
+        match perturbation {
+            Some(perturbation) if domain.scale < FloatExp::from_f64(FLOATEXP_SCALE) => {
+                Kernel::FloatExpPerturbation {
+                    perturbation,
+                    scale: domain.scale,
+                }
+            }
+            Some(perturbation) => Kernel::Perturbation {
+                perturbation,
+                scale: domain.scale.to_f64(),
+            },
+            None => Kernel::DoubleDouble(Direct::new(domain, options)),
+        }
+    }
+
+    // Iterations of a row of pixels starting at the start canvas point,
+    // single and double precision kernels batch them when the fractal
+    // allows it
+    pub fn get_row_iterations(
+        &self,
+        domain: &Domain,
+        start: Point,
+        row: &mut [Option<IterationsMaybe>],
+        options: &Options,
+    ) {
+        let positions: Vec<Point> = (0..row.len())
+            .map(|x| domain.position(start + Point::new(x as f64, 0.)))
+            .collect();
+        self.get_iterations_along(&positions, row, options);
+    }
+
+    pub fn get_iterations_along(
+        &self,
+        positions: &[Point],
+        results: &mut [Option<IterationsMaybe>],
+        options: &Options,
+    ) {
+        match self {
+            Kernel::Single(direct) if batch::supports(options) => {
+                direct.get_batched_iterations(positions, results, options)
+            }
+            Kernel::Double(direct) if batch::supports(options) => {
+                direct.get_batched_iterations(positions, results, options)
+            }
+            _ => {
+                for (result, position) in results.iter_mut().zip(positions) {
+                    *result = Some(self.get_iterations_at(*position, options));
+                }
+            }
+        }
+    }
+
+    pub fn get_iterations_at(&self, position: Point, options: &Options) -> IterationsMaybe {
+        match self {
+            Kernel::Single(direct) => direct.get_iterations_at(position, options),
+            Kernel::Double(direct) => direct.get_iterations_at(position, options),
+            Kernel::DoubleDouble(direct) => direct.get_iterations_at(position, options),
+            Kernel::Perturbation {
+                perturbation,
+                scale,
+            } => perturbation.get_iterations_at(position, *scale, options),
+            Kernel::FloatExpPerturbation {
+                perturbation,
+                scale,
+            } => perturbation.get_iterations_at_extended(position, *scale, options),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use wasm_bindgen::prelude::*;
+
+use crate::pixel::Pixel;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Blend {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Add,
+}
+
+// What a layer colours the pixels with
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum LayerSource {
+    // Smooth iteration count through the palette or hue rotation
+    Iterations,
+    // Orbit average, the iteration count when the average is disabled
+    Average,
+    // Grey shading of the iteration field, escaped pixels only
+    Lighting,
+    // Black on the basin boundaries, boundary pixels only
+    Boundary,
+}
+
+// Colouring composited over the layers below it
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub struct Layer {
+    pub source: LayerSource,
+    pub blend: Blend,
+    pub opacity: f64,
+}
+
+#[wasm_bindgen]
+impl Layer {
+    pub fn new(source: LayerSource, blend: Blend, opacity: f64) -> Layer {
+        Layer {
+            source,
+            blend,
+            opacity,
+        }
+    }
+}
+
+impl Layer {
+    pub fn composite(&self, base: &Pixel, top: &Pixel) -> Pixel {
+        let opacity = self.opacity.clamp(0., 1.);
+        let channel = |base: u8, top: u8| {
+            let (b, t) = (f64::from(base) / 255., f64::from(top) / 255.);
+            let blended = match self.blend {
+                Blend::Normal => t,
+                Blend::Multiply => b * t,
+                Blend::Screen => 1. - (1. - b) * (1. - t),
+                Blend::Overlay => {
+                    if b < 0.5 {
+                        2. * b * t
+                    } else {
+                        1. - 2. * (1. - b) * (1. - t)
+                    }
+                }
+                Blend::Add => (b + t).min(1.),
+            };
+            255. * (b + (blended - b) * opacity)
+        };
+        Pixel::from_f64(
+            channel(base.r, top.r),
+            channel(base.g, top.g),
+            channel(base.b, top.b),
+        )
+    }
+}
@@ -1,13 +1,53 @@
+mod batch;
+mod bigfloat;
+mod clock;
+mod colorspace;
 mod domain;
+mod doubledouble;
+mod floatexp;
 mod fractals;
+mod histogram;
+mod kernel;
+mod layers;
+mod lighting;
+mod palette;
+mod palette_formats;
+mod perturbation;
 mod pixel;
 mod point;
+mod sampling;
+mod scalar;
+mod strategy;
+use std::mem;
+use std::ops::Range;
 use wasm_bindgen::prelude::*;
 
+pub use crate::bigfloat::{BigFloat, BigPoint};
+pub use crate::colorspace::ColorSpace;
 pub use crate::domain::Domain;
-pub use crate::fractals::{Colorization, Fractal, Options, Variant};
+pub use crate::doubledouble::{DoubleDouble, DoubleDoubleComplex};
+pub use crate::floatexp::FloatExp;
+pub use crate::fractals::{
+    Average, Coloring, Colorization, Fractal, Iterations, Options, RootColor, RootShading, Variant,
+};
+pub use crate::histogram::Histogram;
+pub use crate::kernel::Arithmetic;
+pub use crate::layers::{Blend, Layer, LayerSource};
+pub use crate::palette::{Interpolation, Palette, Wrap};
 pub use crate::pixel::Pixel;
 pub use crate::point::Point;
+pub use crate::sampling::{Filter, Sampling, Supersampling, Upscale};
+pub use crate::scalar::Scalar;
+pub use crate::strategy::Strategy;
+
+use crate::fractals::IterationsMaybe;
+use crate::kernel::{needs_perturbation, Kernel};
+use crate::perturbation::Perturbation;
+use crate::sampling::Sample;
+use crate::strategy::Work;
+
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+use rayon::prelude::*;
 
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
@@ -20,17 +60,281 @@ pub fn set_panic_hook() {
 
 #[wasm_bindgen]
 pub struct DomainOption {
-    pub origin: Point,
-    pub scale: f64,
+    x: String,
+    y: String,
+    scale: String,
+}
+
+#[wasm_bindgen]
+impl DomainOption {
+    #[wasm_bindgen(getter)]
+    pub fn x(&self) -> String {
+        self.x.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn y(&self) -> String {
+        self.y.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn scale(&self) -> String {
+        self.scale.clone()
+    }
+}
+
+// Fills every row with the values computed for its pixels, given the index
+// of its first pixel. Rows are spread over all cores in native builds with
+// the parallel feature
+fn compute_rows<R: Send>(
+    values: &mut [R],
+    width: usize,
+    compute: impl Fn(usize, &mut [R]) + Send + Sync,
+) {
+    if width == 0 {
+        return;
+    }
+    let row = |(y, row): (usize, &mut [R])| compute(y * width, row);
+    #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+    values.par_chunks_mut(width).enumerate().for_each(row);
+    #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+    values.chunks_mut(width).enumerate().for_each(row);
+}
+
+// Layers are composited over black, a layer whose source is missing at a
+// pixel leaves it untouched
+fn composite(
+    fractal: &dyn Fractal<f64>,
+    options: &Options,
+    coloring: &Coloring,
+    iterations: IterationsMaybe,
+    results: &[Option<IterationsMaybe>],
+    width: usize,
+    index: usize,
+) -> Pixel {
+    let mut color = Pixel::black();
+    for layer in coloring.layers {
+        let top = match layer.source {
+            LayerSource::Iterations => Some(fractal.get_pixel_for_iteration(
+                iterations.map(|iterations| Iterations {
+                    average: None,
+                    ..iterations
+                }),
+                options,
+                coloring,
+            )),
+            LayerSource::Average => {
+                Some(fractal.get_pixel_for_iteration(iterations, options, coloring))
+            }
+            LayerSource::Lighting if iterations.is_some() => Some(lighting::shade(
+                Pixel::new(255, 255, 255, 255),
+                results,
+                width,
+                index,
+                options,
+            )),
+            LayerSource::Boundary if lighting::on_boundary(results, width, index) => {
+                Some(Pixel::black())
+            }
+            _ => None,
+        };
+        if let Some(top) = top {
+            color = layer.composite(&color, &top);
+        }
+    }
+    color
+}
+
+// Colour of iterations sampled in the pixel at index, before the frame wide
+// effects
+fn color_at(
+    fractal: &dyn Fractal<f64>,
+    options: &Options,
+    coloring: &Coloring,
+    iterations: IterationsMaybe,
+    results: &[Option<IterationsMaybe>],
+    width: usize,
+    index: usize,
+) -> Pixel {
+    if coloring.layers.is_empty() {
+        fractal.get_pixel_for_iteration(iterations, options, coloring)
+    } else {
+        composite(
+            fractal, options, coloring, iterations, results, width, index,
+        )
+    }
+}
+
+// Second render pass turning every computed result into its pixel
+fn colorize(
+    fractal: &dyn Fractal<f64>,
+    options: &Options,
+    coloring: Coloring,
+    results: &[Option<IterationsMaybe>],
+    samples: &[Vec<Sample>],
+    width: usize,
+    data: &mut [Pixel],
+) {
+    let histogram = if options.colorization == Colorization::Histogram {
+        Some(Histogram::new(
+            results.iter().flatten().flatten(),
+            options.precision,
+        ))
+    } else {
+        None
+    };
+    let coloring = Coloring {
+        histogram: histogram.as_ref(),
+        ..coloring
+    };
+    for (i, pixel) in data.iter_mut().enumerate().take(results.len()) {
+        if results[i].is_some() {
+            pixel.from(final_color(
+                fractal, options, &coloring, results, samples, width, i,
+            ));
+        }
+    }
+}
+
+// Colour of the computed pixel at index with its samples, lighting and
+// basin boundary
+fn final_color(
+    fractal: &dyn Fractal<f64>,
+    options: &Options,
+    coloring: &Coloring,
+    results: &[Option<IterationsMaybe>],
+    samples: &[Vec<Sample>],
+    width: usize,
+    index: usize,
+) -> Pixel {
+    let iterations = results[index].flatten();
+    let color_at = |iterations| {
+        color_at(
+            fractal, options, coloring, iterations, results, width, index,
+        )
+    };
+    let mut color = match samples.get(index) {
+        Some(samples) if !samples.is_empty() => sampling::reconstruct(samples, color_at),
+        _ => color_at(iterations),
+    };
+    if options.lighting && iterations.is_some() {
+        color = lighting::shade(color, results, width, index, options);
+    }
+    if options.basin_boundary > 0. && lighting::on_boundary(results, width, index) {
+        let darken = |c: u8| f64::from(c) * (1. - options.basin_boundary);
+        color = Pixel::from_f64(darken(color.r), darken(color.g), darken(color.b));
+    }
+    color
+}
+
+// Moves every value of the frame to dx, dy pixels from where it was, the
+// exposed ones being reset
+fn scroll<T>(values: &mut [T], width: usize, dx: isize, dy: isize, reset: impl Fn() -> T) {
+    let height = values.len() / width;
+    let mut previous: Vec<T> = values
+        .iter_mut()
+        .map(|value| mem::replace(value, reset()))
+        .collect();
+    for (i, value) in values.iter_mut().enumerate() {
+        let x = (i % width) as isize - dx;
+        let y = (i / width) as isize - dy;
+        if x >= 0 && y >= 0 && x < width as isize && y < height as isize {
+            *value = mem::replace(&mut previous[y as usize * width + x as usize], reset());
+        }
+    }
+}
+
+// Size of the blocks filled by the first refinement pass, halved by each
+// following pass down to single pixels
+const REFINEMENT_BLOCK: usize = 16;
+
+// Most pixels a strategy sets between two readings of the clock, colouring
+// them must fit in the budget too
+const SLICE: usize = 4096;
+
+// Where render_for resumes
+#[derive(Default)]
+struct Cursor {
+    stage: Stage,
+    // Next row of the stage
+    row: usize,
+    // Results computed so far
+    computed: usize,
+    // Their histogram counts, for histogram colourings
+    counts: Vec<usize>,
+}
+
+#[derive(Default)]
+enum Stage {
+    #[default]
+    Start,
+    // Refinement pass of the pixels strategy
+    Pass(usize),
+    Strategy(strategy::Work),
+    // Frame-wide colourings, once every result is computed
+    Shading,
+    Done,
+}
+
+// Pixels not computed yet take the colour of the top left pixel of the
+// smallest refinement block around them that is computed, unless they hold
+// a placeholder
+fn fill(
+    results: &[Option<IterationsMaybe>],
+    placeholders: &[bool],
+    width: usize,
+    data: &mut [Pixel],
+    rows: Range<usize>,
+) {
+    for i in rows.start * width..(rows.end * width).min(results.len()) {
+        if results[i].is_some() || placeholders[i] {
+            continue;
+        }
+        let (x, y) = (i % width, i / width);
+        let mut step = 2;
+        while step <= REFINEMENT_BLOCK {
+            let anchor = (y - y % step) * width + x - x % step;
+            if results[anchor].is_some() {
+                let color = Pixel::new(data[anchor].r, data[anchor].g, data[anchor].b, 255);
+                data[i].from(color);
+                break;
+            }
+            step *= 2;
+        }
+    }
+}
+
+// Preview grid covering the canvas, its last row and column overflow it when
+// the resolution doesn't divide the canvas size
+fn preview_size(width: usize, height: usize, resolution: usize) -> (usize, usize) {
+    let resolution = resolution.max(1);
+    (width.div_ceil(resolution), height.div_ceil(resolution))
 }
 
 #[wasm_bindgen]
 pub struct Frustal {
     data: Vec<Pixel>,
+    // Iterations of each pixel, None until computed
+    results: Vec<Option<IterationsMaybe>>,
+    // Supersampled iterations replacing the result colour, empty if none
+    samples: Vec<Vec<Sample>>,
+    // Pixels showing the previous frame until they are computed
+    placeholders: Vec<bool>,
+    // Where render_for resumes
+    cursor: Cursor,
+    // Histogram render_for colours with, updated between its stages
+    histogram: Option<Histogram>,
     preview_data: Vec<Pixel>,
+    preview_results: Vec<Option<IterationsMaybe>>,
     pub options: Options,
+    // Options the stored results were computed with
+    computed: Options,
     domain: Domain,
-    fractal: Box<dyn Fractal>,
+    fractal: Box<dyn Fractal<f64>>,
+    perturbation: Option<Perturbation>,
+    palette: Option<Palette>,
+    root_colors: Vec<RootColor>,
+    layers: Vec<Layer>,
     preview_resolution: usize,
 }
 
@@ -38,27 +342,55 @@ pub struct Frustal {
 impl Frustal {
     pub fn new(width: usize, height: usize, preview_resolution: usize) -> Frustal {
         let variant = Variant::Mandelbrot;
+        let (preview_width, preview_height) = preview_size(width, height, preview_resolution);
+        let options = Options {
+            variant,
+            smooth: true,
+            precision: 25,
+            arithmetic: Arithmetic::Auto,
+            strategy: Strategy::Pixels,
+            order: 2,
+            const_real: 0.0,
+            const_imaginary: 0.0,
+            average: Average::None,
+            stripe_density: 5.,
+            colorization: Colorization::Relative,
+            black_threshold: 0.1,
+            white_threshold: 0.9,
+            overexposure: 1.,
+            color_rotation: 0.1,
+            color_base: 0.1,
+            color_space: ColorSpace::Hwb,
+            root_shading: RootShading::Iterations,
+            basin_boundary: 0.,
+            lighting: false,
+            light_azimuth: 45.,
+            light_elevation: 45.,
+            light_height: 1.,
+            ambient: 0.3,
+            diffuse: 0.7,
+            specular: 0.3,
+            shininess: 20.,
+        };
         Frustal {
             data: (0..width * height).map(|_| Pixel::void()).collect(),
-            preview_data: (0..(width * height) / (preview_resolution * preview_resolution))
+            results: vec![None; width * height],
+            samples: vec![Vec::new(); width * height],
+            placeholders: vec![false; width * height],
+            cursor: Cursor::default(),
+            histogram: None,
+            preview_data: (0..preview_width * preview_height)
                 .map(|_| Pixel::void())
                 .collect(),
-            options: Options {
-                variant,
-                smooth: true,
-                precision: 25,
-                order: 2,
-                const_real: 0.0,
-                const_imaginary: 0.0,
-                colorization: Colorization::Relative,
-                black_threshold: 0.1,
-                white_threshold: 0.9,
-                overexposure: 1.,
-                color_rotation: 0.1,
-                color_base: 0.1,
-            },
+            preview_results: vec![None; preview_width * preview_height],
+            options,
+            computed: options,
             domain: Domain::new(Point::new(width as f64, height as f64)),
             fractal: variant.get_fractal(),
+            perturbation: None,
+            palette: None,
+            root_colors: Vec::new(),
+            layers: Vec::new(),
             preview_resolution,
         }
     }
@@ -66,36 +398,134 @@ impl Frustal {
     pub fn resize(&mut self, width: usize, height: usize) {
         self.domain.resize(Point::new(width as f64, height as f64));
         self.data = (0..width * height).map(|_| Pixel::void()).collect();
-        self.preview_data = (0..(width * height)
-            / (self.preview_resolution * self.preview_resolution))
-            .map(|_| Pixel::void())
-            .collect();
+        self.results = vec![None; width * height];
+        self.samples = vec![Vec::new(); width * height];
+        self.placeholders = vec![false; width * height];
+        self.cursor = Cursor::default();
+        self.resize_preview(self.preview_resolution);
     }
 
     pub fn resize_preview(&mut self, preview_resolution: usize) {
         self.preview_resolution = preview_resolution;
-        self.preview_data = (0..((self.domain.size.x as usize) * (self.domain.size.y as usize))
-            / (self.preview_resolution * self.preview_resolution))
-            .map(|_| Pixel::void())
-            .collect();
+        let (width, height) = self.preview_size();
+        self.preview_data = (0..width * height).map(|_| Pixel::void()).collect();
+        self.preview_results = vec![None; width * height];
+    }
+
+    pub fn preview_width(&self) -> usize {
+        self.preview_size().0
+    }
+
+    pub fn preview_height(&self) -> usize {
+        self.preview_size().1
+    }
+
+    fn preview_size(&self) -> (usize, usize) {
+        preview_size(
+            self.domain.size.x as usize,
+            self.domain.size.y as usize,
+            self.preview_resolution,
+        )
     }
 
     pub fn reset_data(&mut self) {
         for point in &mut self.data {
             *point = Pixel::void()
         }
+        for result in &mut self.results {
+            *result = None
+        }
+        for samples in &mut self.samples {
+            samples.clear()
+        }
+        for placeholder in &mut self.placeholders {
+            *placeholder = false
+        }
+        self.cancel();
+    }
+
+    // Restarts render_for from its first pass, computed pixels are kept
+    pub fn cancel(&mut self) {
+        self.cursor = Cursor::default();
     }
 
+    // Shifts by whole pixels keep the pixels still in the frame, leaving
+    // only the exposed ones to be computed
     pub fn shift_domain(&mut self, point: Point) {
-        self.domain.shift(point)
+        self.domain.shift(point);
+        self.perturbation = None;
+        let width = self.domain.size.x as usize;
+        if point.x.fract() != 0. || point.y.fract() != 0. || width == 0 {
+            self.reset_data();
+            return;
+        }
+        let (dx, dy) = (-point.x as isize, -point.y as isize);
+        scroll(&mut self.results, width, dx, dy, || None);
+        scroll(&mut self.samples, width, dx, dy, Vec::new);
+        scroll(&mut self.data, width, dx, dy, Pixel::void);
+        scroll(&mut self.placeholders, width, dx, dy, || false);
+        self.cancel();
     }
 
-    pub fn change_domain(&mut self, x: f64, y: f64, scale: f64) {
-        self.domain.change(Point::new(x, y), scale)
+    // Values are decimal strings to keep deep zoom locations exact,
+    // unparsable values and scales that aren't finite and positive leave
+    // the current value untouched
+    pub fn change_domain(&mut self, x: &str, y: &str, scale: &str) {
+        let origin = BigPoint::new(
+            x.parse().unwrap_or_else(|_| self.domain.origin.x.clone()),
+            y.parse().unwrap_or_else(|_| self.domain.origin.y.clone()),
+        );
+        let scale = scale
+            .parse::<FloatExp>()
+            .ok()
+            .filter(|scale| scale.mantissa().is_finite() && *scale > FloatExp::from_f64(0.))
+            .unwrap_or(self.domain.scale);
+        self.domain.change(origin, scale);
+        self.perturbation = None;
+        self.reset_data();
+    }
+
+    // Zooms by ratio around the canvas point center, pixels landing exactly
+    // on previous ones are kept, as when zooming by whole factors around a
+    // pixel centre. The others show the previous pixel covering them until
+    // computed
+    pub fn scale_domain(&mut self, ratio: f64, center: Point) {
+        self.domain.scale_around(ratio, center);
+        self.perturbation = None;
+        let results = mem::replace(&mut self.results, vec![None; self.data.len()]);
+        let data: Vec<Pixel> = self
+            .data
+            .iter_mut()
+            .map(|pixel| mem::replace(pixel, Pixel::void()))
+            .collect();
+        let previous_placeholders =
+            mem::replace(&mut self.placeholders, vec![false; self.data.len()]);
+        for samples in &mut self.samples {
+            samples.clear()
+        }
+        self.cancel();
+        for i in 0..self.data.len() {
+            let point = self.domain.point(i);
+            let previous = center + (point - center) * Point::new(ratio, ratio);
+            let j = match self.domain.index(previous) {
+                Some(j) if results[j].is_some() || previous_placeholders[j] => j,
+                _ => continue,
+            };
+            let offset = previous - self.domain.point(j);
+            let exact = offset.x.abs() < 1e-9 && offset.y.abs() < 1e-9;
+            if exact && results[j].is_some() {
+                self.results[i] = results[j];
+            } else {
+                self.placeholders[i] = true;
+            }
+            self.data[i].from(Pixel::new(data[j].r, data[j].g, data[j].b, data[j].a));
+        }
     }
 
     pub fn zoom_domain(&mut self, factor: f64, center: Point) {
-        self.domain.zoom(factor, center)
+        self.domain.zoom(factor, center);
+        self.perturbation = None;
+        self.reset_data();
     }
 
     pub fn data_ptr(&mut self) -> *const Pixel {
@@ -106,45 +536,553 @@ impl Frustal {
         self.preview_data.as_ptr()
     }
 
+    // The reference orbit is costly, compute it once per domain and options.
+    // Results computed with other options, set directly, are dropped
+    fn prepare(&mut self) {
+        if !self.computed.same_iterations(&self.options) {
+            self.clear_results();
+        }
+        if self.perturbation.is_none() && needs_perturbation(&self.domain, &self.options) {
+            self.perturbation = Perturbation::new(&self.domain, &self.options);
+        }
+    }
+
+    fn colorize(&mut self) {
+        colorize(
+            self.fractal.as_ref(),
+            &self.options,
+            Coloring {
+                palette: self.palette.as_ref(),
+                histogram: None,
+                roots: &self.root_colors,
+                layers: &self.layers,
+            },
+            &self.results,
+            &self.samples,
+            self.domain.size.x as usize,
+            &mut self.data,
+        );
+        fill(
+            &self.results,
+            &self.placeholders,
+            self.domain.size.x as usize,
+            &mut self.data,
+            0..self.domain.size.y as usize,
+        );
+    }
+
+    fn colorize_preview(&mut self) {
+        colorize(
+            self.fractal.as_ref(),
+            &self.options,
+            Coloring {
+                palette: self.palette.as_ref(),
+                histogram: None,
+                roots: &self.root_colors,
+                layers: &self.layers,
+            },
+            &self.preview_results,
+            &[],
+            self.preview_width().max(1),
+            &mut self.preview_data,
+        );
+    }
+
+    pub fn recolor(&mut self) {
+        self.colorize();
+        self.colorize_preview();
+    }
+
+    // Advances the colours by step cycles of the palette, or of the hue
+    // circle without palette, and colors the stored results again
+    pub fn cycle(&mut self, step: f64) {
+        self.options.color_base += 360. * step;
+        if let Some(palette) = &mut self.palette {
+            palette.offset += step;
+        }
+        for root in &mut self.root_colors {
+            if let RootColor::Palette(palette) = root {
+                palette.offset += step;
+            }
+        }
+        self.recolor();
+    }
+
+    // Pixels already computed are kept
     pub fn render(&mut self) {
-        for (i, point) in self.domain.iter().enumerate() {
-            let pixel = self.fractal.get_pixel_at_point(point, &self.options);
-            self.data[i].from(pixel);
+        self.prepare();
+        let kernel = Kernel::new(self.perturbation.as_ref(), &self.domain, &self.options);
+        let (domain, options) = (&self.domain, &self.options);
+        match options.strategy {
+            Strategy::Pixels => {
+                compute_rows(&mut self.results, domain.size.x as usize, |index, row| {
+                    // Pixels left by a pan or a zoom are still batched
+                    let missing: Vec<usize> =
+                        (0..row.len()).filter(|&x| row[x].is_none()).collect();
+                    let positions: Vec<Point> = missing
+                        .iter()
+                        .map(|&x| domain.position(domain.point(index + x)))
+                        .collect();
+                    let mut computed = vec![None; missing.len()];
+                    kernel.get_iterations_along(&positions, &mut computed, options);
+                    for (x, result) in missing.into_iter().zip(computed) {
+                        row[x] = result;
+                    }
+                });
+            }
+            Strategy::Subdivision | Strategy::BoundaryTracing => {
+                if let Some(work) = Work::new(options.strategy, domain) {
+                    let mut touched = Vec::new();
+                    strategy::resume(
+                        work,
+                        &kernel,
+                        domain,
+                        options,
+                        &mut self.results,
+                        &mut touched,
+                        |_| false,
+                    );
+                }
+            }
         }
+        self.colorize();
     }
 
+    // Every preview pixel is iterated at the centre of the block of canvas
+    // pixels it covers
     pub fn preview_render(&mut self) {
         set_panic_hook();
+        let (width, _) = self.preview_size();
+        let resolution = self.preview_resolution.max(1) as f64;
+        self.prepare();
+        let kernel = Kernel::new(self.perturbation.as_ref(), &self.domain, &self.options);
+        let (domain, options) = (&self.domain, &self.options);
+        for (y, row) in self.preview_results.chunks_mut(width.max(1)).enumerate() {
+            let positions: Vec<Point> = (0..row.len())
+                .map(|x| {
+                    let center = Point::new(x as f64 + 0.5, y as f64 + 0.5);
+                    domain.position(center * Point::new(resolution, resolution))
+                })
+                .collect();
+            kernel.get_iterations_along(&positions, row, options);
+        }
+        self.colorize_preview();
+    }
+
+    // Shows the preview in the pixels that are neither computed nor
+    // placeholders, they stay placeholders until computed
+    pub fn upscale_preview(&mut self, upscale: Upscale) {
+        let (width, height) = self.preview_size();
+        if width == 0 || height == 0 {
+            return;
+        }
+        let resolution = self.preview_resolution.max(1) as f64;
+        for i in 0..self.data.len() {
+            if self.results[i].is_some() || self.placeholders[i] {
+                continue;
+            }
+            // In preview pixels, their centres being integral
+            let point =
+                self.domain.point(i) / Point::new(resolution, resolution) - Point::new(0.5, 0.5);
+            self.data[i].from(upscale.sample(&self.preview_data, width, height, point));
+            self.placeholders[i] = true;
+        }
+    }
+
+    // The histogram, lighting and basin boundaries change with every pass
+    // so all computed pixels are colored again with them
+    fn frame_wide(&self) -> bool {
+        self.options.colorization == Colorization::Histogram
+            || self.options.lighting
+            || self.options.basin_boundary > 0.
+            || self.layers.iter().any(|layer| {
+                layer.source == LayerSource::Lighting || layer.source == LayerSource::Boundary
+            })
+    }
+
+    pub fn passes(&self) -> usize {
+        REFINEMENT_BLOCK.trailing_zeros() as usize + 1
+    }
+
+    // Computes the pixels on the grid of the pass that aren't yet, the
+    // others are filled from the computed ones so every pass gives a
+    // complete image of increasing resolution
+    pub fn partial_render(&mut self, pass: usize) {
+        let step = (REFINEMENT_BLOCK >> pass).max(1);
         let width = self.domain.size.x as usize;
-        let height = self.domain.size.y as usize;
-        let len = (width * height) / (self.preview_resolution * self.preview_resolution);
-        let mut j = 0;
-        for (i, point) in self.domain.iter().enumerate() {
-            if i % self.preview_resolution != 0
-                || ((i + self.preview_resolution - 1) / width) % self.preview_resolution != 0
-                || j >= len
+        self.prepare();
+        let kernel = Kernel::new(self.perturbation.as_ref(), &self.domain, &self.options);
+        let coloring = Coloring {
+            palette: self.palette.as_ref(),
+            histogram: None,
+            roots: &self.root_colors,
+            layers: &self.layers,
+        };
+        let frame_wide = self.frame_wide();
+        for (i, position) in self.domain.iter().enumerate() {
+            if !(i % width).is_multiple_of(step)
+                || !(i / width).is_multiple_of(step)
+                || self.results[i].is_some()
             {
                 continue;
             }
-            let pixel = self.fractal.get_pixel_at_point(point, &self.options);
-            self.preview_data[j].from(pixel);
-            j += 1;
+            self.results[i] = Some(kernel.get_iterations_at(position, &self.options));
+            if !frame_wide {
+                self.data[i].from(color_at(
+                    self.fractal.as_ref(),
+                    &self.options,
+                    &coloring,
+                    self.results[i].flatten(),
+                    &self.results,
+                    width,
+                    i,
+                ));
+            }
+        }
+        if frame_wide {
+            self.colorize();
+        } else {
+            let height = self.domain.size.y as usize;
+            fill(
+                &self.results,
+                &self.placeholders,
+                width,
+                &mut self.data,
+                0..height,
+            );
         }
     }
 
-    pub fn partial_render(&mut self, skip: usize, index: usize) {
-        for (i, point) in self.domain.iter().enumerate() {
-            if (i + index) % skip != 0 {
-                continue;
+    // Renders until budget_ms is spent, a step at least, and returns the
+    // share of the work done, 1 once the render is complete. Every call
+    // leaves an image and the next one resumes where it stopped. The pixels
+    // strategy refines the frame pass after pass, others show the pixels as
+    // they get computed. Frame-wide colourings are applied row by row once
+    // every result is computed
+    pub fn render_for(&mut self, budget_ms: f64) -> f64 {
+        let deadline = clock::now() + budget_ms;
+        self.prepare();
+        let width = self.domain.size.x as usize;
+        let height = self.domain.size.y as usize;
+        let passes = self.passes();
+        let frame_wide = self.frame_wide();
+        let mut touched = Vec::new();
+        loop {
+            self.cursor.stage = match mem::take(&mut self.cursor.stage) {
+                Stage::Start => {
+                    let work = Work::new(self.options.strategy, &self.domain);
+                    let computed: Vec<usize> = (0..self.results.len())
+                        .filter(|&i| self.results[i].is_some())
+                        .collect();
+                    self.cursor = Cursor {
+                        counts: match self.options.colorization {
+                            Colorization::Histogram => vec![0; self.options.precision + 1],
+                            _ => vec![],
+                        },
+                        ..Cursor::default()
+                    };
+                    self.count(&computed);
+                    self.update_histogram();
+                    match work {
+                        Some(work) => Stage::Strategy(work),
+                        None => Stage::Pass(0),
+                    }
+                }
+                Stage::Pass(pass) if self.cursor.row >= height => {
+                    self.cursor.row = 0;
+                    self.update_histogram();
+                    if pass + 1 < passes {
+                        Stage::Pass(pass + 1)
+                    } else if frame_wide {
+                        Stage::Shading
+                    } else {
+                        Stage::Done
+                    }
+                }
+                Stage::Pass(pass) => {
+                    let step = (REFINEMENT_BLOCK >> pass).max(1);
+                    let row = self.cursor.row * width;
+                    let missing: Vec<usize> = (row..row + width)
+                        .step_by(step)
+                        .filter(|&i| self.results[i].is_none())
+                        .collect();
+                    let positions: Vec<Point> = missing
+                        .iter()
+                        .map(|&i| self.domain.position(self.domain.point(i)))
+                        .collect();
+                    let mut computed = vec![None; missing.len()];
+                    Kernel::new(self.perturbation.as_ref(), &self.domain, &self.options)
+                        .get_iterations_along(&positions, &mut computed, &self.options);
+                    for (&i, result) in missing.iter().zip(computed) {
+                        self.results[i] = result;
+                    }
+                    self.count(&missing);
+                    self.color(&missing);
+                    // Only the blocks anchored on this row change
+                    let rows = self.cursor.row..(self.cursor.row + step).min(height);
+                    fill(
+                        &self.results,
+                        &self.placeholders,
+                        width,
+                        &mut self.data,
+                        rows,
+                    );
+                    self.cursor.row += step;
+                    Stage::Pass(pass)
+                }
+                Stage::Strategy(work) => {
+                    // Pixels are colored by slices whose cost counts
+                    let work = strategy::resume(
+                        work,
+                        &Kernel::new(self.perturbation.as_ref(), &self.domain, &self.options),
+                        &self.domain,
+                        &self.options,
+                        &mut self.results,
+                        &mut touched,
+                        |touched| touched.len() >= SLICE || clock::now() >= deadline,
+                    );
+                    self.count(&touched);
+                    self.color(&touched);
+                    touched.clear();
+                    match work {
+                        Some(work) => Stage::Strategy(work),
+                        None if frame_wide => {
+                            self.update_histogram();
+                            Stage::Shading
+                        }
+                        None => Stage::Done,
+                    }
+                }
+                Stage::Shading if self.cursor.row < height => {
+                    let row = self.cursor.row * width;
+                    self.shade(row..row + width);
+                    self.cursor.row += 1;
+                    Stage::Shading
+                }
+                Stage::Shading | Stage::Done => {
+                    self.cursor.stage = Stage::Done;
+                    break;
+                }
+            };
+            if clock::now() >= deadline {
+                break;
             }
-            let pixel = self.fractal.get_pixel_at_point(point, &self.options);
-            self.data[i].from(pixel);
         }
+        self.progress(frame_wide)
+    }
+
+    // Share of the work done, frame-wide colourings taking a tenth of it.
+    // Below 1 until the render is complete, whatever was reused
+    fn progress(&self, frame_wide: bool) -> f64 {
+        let len = self.results.len().max(1) as f64;
+        let height = self.domain.size.y.max(1.);
+        let computed = (self.cursor.computed as f64 / len).min(1.);
+        let share = match self.cursor.stage {
+            Stage::Done => return 1.,
+            Stage::Shading => 0.9 + 0.1 * self.cursor.row as f64 / height,
+            _ if frame_wide => 0.9 * computed,
+            _ => computed,
+        };
+        share.min(0.99)
+    }
+
+    // Counts the results computed at indices
+    fn count(&mut self, indices: &[usize]) {
+        self.cursor.computed += indices.len();
+        if !self.cursor.counts.is_empty() {
+            let results = &self.results;
+            let iterations = indices
+                .iter()
+                .filter_map(|&i| results[i].as_ref().and_then(Option::as_ref));
+            Histogram::count(&mut self.cursor.counts, iterations);
+        }
+    }
+
+    // Histogram of the results computed so far for histogram colourings
+    fn update_histogram(&mut self) {
+        self.histogram = if self.cursor.counts.is_empty() {
+            None
+        } else {
+            Some(Histogram::from_counts(&self.cursor.counts))
+        };
+    }
+
+    // Colours the computed pixels at indices, with the last histogram
+    fn color(&mut self, indices: &[usize]) {
+        let width = self.domain.size.x as usize;
+        let coloring = Coloring {
+            palette: self.palette.as_ref(),
+            histogram: self.histogram.as_ref(),
+            roots: &self.root_colors,
+            layers: &self.layers,
+        };
+        for &i in indices {
+            self.data[i].from(final_color(
+                self.fractal.as_ref(),
+                &self.options,
+                &coloring,
+                &self.results,
+                &self.samples,
+                width,
+                i,
+            ));
+        }
+    }
+
+    fn shade(&mut self, indices: Range<usize>) {
+        let indices: Vec<usize> = indices.filter(|&i| self.results[i].is_some()).collect();
+        self.color(&indices);
     }
 
-    pub fn sync_options(&mut self, options: &Options) {
+    // Computes the rectangle of the canvas starting at x, y, clipped to it
+    pub fn render_tile(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        self.prepare();
+        let kernel = Kernel::new(self.perturbation.as_ref(), &self.domain, &self.options);
+        let canvas_width = self.domain.size.x as usize;
+        let canvas_height = self.domain.size.y as usize;
+        let rows = y.min(canvas_height)..(y + height).min(canvas_height);
+        let columns = x.min(canvas_width)..(x + width).min(canvas_width);
+        for row in rows.clone() {
+            let start = row * canvas_width + columns.start;
+            let end = row * canvas_width + columns.end;
+            kernel.get_row_iterations(
+                &self.domain,
+                self.domain.point(start),
+                &mut self.results[start..end],
+                &self.options,
+            );
+            for samples in &mut self.samples[start..end] {
+                samples.clear()
+            }
+        }
+        if self.frame_wide() {
+            self.colorize();
+            return;
+        }
+        let coloring = Coloring {
+            palette: self.palette.as_ref(),
+            histogram: None,
+            roots: &self.root_colors,
+            layers: &self.layers,
+        };
+        for row in rows {
+            for i in row * canvas_width + columns.start..row * canvas_width + columns.end {
+                self.data[i].from(color_at(
+                    self.fractal.as_ref(),
+                    &self.options,
+                    &coloring,
+                    self.results[i].flatten(),
+                    &self.results,
+                    canvas_width,
+                    i,
+                ));
+            }
+        }
+    }
+
+    // Antialiasing pass over a finished render, too slow for interaction
+    pub fn supersample(&mut self, supersampling: &Supersampling) {
+        self.prepare();
+        let kernel = Kernel::new(self.perturbation.as_ref(), &self.domain, &self.options);
+        let (domain, options) = (&self.domain, &self.options);
+        let (results, data) = (&self.results, &self.data);
+        let width = domain.size.x as usize;
+        compute_rows(&mut self.samples, width, |index, row| {
+            for (i, samples) in (index..).zip(row) {
+                if results[i].is_none() || !supersampling.needs_samples(data, width, i) {
+                    continue;
+                }
+                let point = domain.point(i);
+                *samples = supersampling
+                    .offsets(i)
+                    .into_iter()
+                    .map(|(offset, weight)| Sample {
+                        weight,
+                        iterations: kernel
+                            .get_iterations_at(domain.position(point + offset), options),
+                    })
+                    .collect();
+            }
+        });
+        self.colorize();
+    }
+
+    // Returns whether a render is needed, when only colouring options
+    // changed the stored results are colored again instead
+    pub fn sync_options(&mut self, options: &Options) -> bool {
+        let same_iterations = self.computed.same_iterations(options);
         self.options = *options;
+        // Colourings render_for applies may have changed too
+        self.cancel();
+        if same_iterations {
+            self.recolor();
+            return false;
+        }
+        self.clear_results();
+        true
+    }
+
+    fn clear_results(&mut self) {
+        self.computed = self.options;
         self.fractal = self.options.variant.get_fractal();
+        self.perturbation = None;
+        for result in self.results.iter_mut().chain(&mut self.preview_results) {
+            *result = None
+        }
+        for samples in &mut self.samples {
+            samples.clear()
+        }
+        self.cancel();
+    }
+
+    // Without palette colours come from the color_base hue rotation
+    pub fn set_palette(&mut self, palette: Option<Palette>) {
+        self.palette = palette;
+    }
+
+    pub fn current_palette(&self) -> Option<Palette> {
+        self.palette.clone()
+    }
+
+    // Number of roots of Newton fractals, 1 for the others
+    pub fn channels(&self) -> usize {
+        self.fractal.channels()
+    }
+
+    fn set_root(&mut self, root: usize, color: RootColor) {
+        if self.root_colors.len() <= root {
+            self.root_colors.resize(root + 1, RootColor::Hue);
+        }
+        self.root_colors[root] = color;
+    }
+
+    pub fn set_root_color(&mut self, root: usize, r: u8, g: u8, b: u8) {
+        self.set_root(
+            root,
+            RootColor::Color([
+                f64::from(r) / 255.,
+                f64::from(g) / 255.,
+                f64::from(b) / 255.,
+            ]),
+        );
+    }
+
+    // Roots without palette get back their hue
+    pub fn set_root_palette(&mut self, root: usize, palette: Option<Palette>) {
+        self.set_root(root, palette.map_or(RootColor::Hue, RootColor::Palette));
+    }
+
+    pub fn reset_root_colors(&mut self) {
+        self.root_colors.clear();
+    }
+
+    // Layers replace the single colouring, from bottom to top
+    pub fn add_layer(&mut self, layer: Layer) {
+        self.layers.push(layer);
+    }
+
+    pub fn clear_layers(&mut self) {
+        self.layers.clear();
     }
 
     pub fn current_options(&self) -> Options {
@@ -153,8 +1091,233 @@ impl Frustal {
 
     pub fn current_domain(&self) -> DomainOption {
         DomainOption {
-            origin: self.domain.origin,
-            scale: self.domain.scale,
+            x: self.domain.origin.x.to_string(),
+            y: self.domain.origin.y.to_string(),
+            scale: self.domain.scale.to_string(),
+        }
+    }
+}
+
+impl Frustal {
+    // Pixels of the rectangle starting at x, y of the canvas written row by
+    // row to pixels, which must hold width * height of them, leaving the
+    // frame untouched. The rectangle can extend past the canvas and frame
+    // wide colourings only see the tile
+    pub fn render_tile_into(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        pixels: &mut [Pixel],
+    ) {
+        assert_eq!(pixels.len(), width * height, "tile buffer size");
+        self.prepare();
+        let kernel = Kernel::new(self.perturbation.as_ref(), &self.domain, &self.options);
+        let mut results = vec![None; width * height];
+        for (row, results) in results.chunks_mut(width.max(1)).enumerate() {
+            kernel.get_row_iterations(
+                &self.domain,
+                self.domain.point(0) + Point::new(x as f64, (y + row) as f64),
+                results,
+                &self.options,
+            );
+        }
+        colorize(
+            self.fractal.as_ref(),
+            &self.options,
+            Coloring {
+                palette: self.palette.as_ref(),
+                histogram: None,
+                roots: &self.root_colors,
+                layers: &self.layers,
+            },
+            &results,
+            &[],
+            width,
+            pixels,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Values = (f64, usize, Option<f64>, Option<f64>);
+
+    fn results(frustal: &Frustal) -> Vec<Option<Option<Values>>> {
+        frustal
+            .results
+            .iter()
+            .map(|result| {
+                result.map(|result| {
+                    result.map(|result| (result.n, result.channel, result.average, result.distance))
+                })
+            })
+            .collect()
+    }
+
+    fn frustal(options: impl Fn(&mut Options)) -> Frustal {
+        let mut frustal = Frustal::new(64, 48, 4);
+        let mut current = frustal.current_options();
+        options(&mut current);
+        frustal.sync_options(&current);
+        frustal.change_domain("-0.5", "0", "1.5");
+        frustal
+    }
+
+    #[test]
+    fn render_drops_results_of_options_set_directly() {
+        let mut changed = frustal(|_| ());
+        changed.render();
+        changed.options.precision = 100;
+        changed.options.variant = Variant::BurningShip;
+        changed.render();
+        let mut fresh = frustal(|options| {
+            options.precision = 100;
+            options.variant = Variant::BurningShip;
+        });
+        fresh.render();
+        assert!(results(&changed) == results(&fresh));
+    }
+
+    #[test]
+    fn empty_canvas_renders() {
+        let mut frustal = Frustal::new(0, 0, 4);
+        frustal.render();
+        frustal.supersample(&Supersampling::new(Sampling::Grid, 4, Filter::Box));
+        assert_eq!(frustal.render_for(16.), 1.);
+    }
+
+    fn pixels(frustal: &Frustal) -> Vec<[u8; 4]> {
+        frustal
+            .data
+            .iter()
+            .map(|pixel| [pixel.r, pixel.g, pixel.b, pixel.a])
+            .collect()
+    }
+
+    fn assert_same(actual: &Frustal, expected: &Frustal) {
+        assert!(results(actual) == results(expected));
+        assert!(pixels(actual) == pixels(expected));
+    }
+
+    // Renders with no budget, a step per call, checking that progress only
+    // reaches 1 once done
+    fn render_in_steps(frustal: &mut Frustal, steps: usize) -> bool {
+        for _ in 0..steps {
+            let progress = frustal.render_for(0.);
+            assert_eq!(progress == 1., matches!(frustal.cursor.stage, Stage::Done));
+            if progress == 1. {
+                return true;
+            }
         }
+        false
+    }
+
+    fn colorings() -> [fn(&mut Options); 3] {
+        [
+            |_| (),
+            |options| options.colorization = Colorization::Histogram,
+            |options| options.lighting = true,
+        ]
+    }
+
+    #[test]
+    fn render_for_matches_render() {
+        for strategy in [
+            Strategy::Pixels,
+            Strategy::Subdivision,
+            Strategy::BoundaryTracing,
+        ] {
+            for coloring in colorings() {
+                let options = |options: &mut Options| {
+                    options.strategy = strategy;
+                    coloring(options);
+                };
+                let mut stepped = frustal(options);
+                assert!(render_in_steps(&mut stepped, 100_000));
+                let mut rendered = frustal(options);
+                rendered.render();
+                assert_same(&stepped, &rendered);
+            }
+        }
+    }
+
+    #[test]
+    fn render_for_restarts_after_changes() {
+        for coloring in colorings() {
+            let mut rendered = frustal(coloring);
+            rendered.render();
+
+            let mut cancelled = frustal(coloring);
+            assert!(!render_in_steps(&mut cancelled, 20));
+            cancelled.cancel();
+            assert!(render_in_steps(&mut cancelled, 100_000));
+            assert_same(&cancelled, &rendered);
+
+            // Iteration then colouring changes
+            let mut synced = frustal(|options| options.precision = 50);
+            assert!(!render_in_steps(&mut synced, 20));
+            assert!(synced.sync_options(&rendered.options));
+            assert!(!render_in_steps(&mut synced, 20));
+            let mut options = rendered.options;
+            options.lighting = !options.lighting;
+            assert!(!synced.sync_options(&options));
+            assert!(!render_in_steps(&mut synced, 20));
+            assert!(!synced.sync_options(&rendered.options));
+            assert!(render_in_steps(&mut synced, 100_000));
+            assert_same(&synced, &rendered);
+        }
+    }
+
+    // Power of two sizes keep every pixel position exact, so reused pixels
+    // match computed ones bit for bit
+    fn exact(x: &str, y: &str, scale: &str) -> Frustal {
+        let mut frustal = Frustal::new(64, 32, 4);
+        let mut options = frustal.current_options();
+        options.arithmetic = Arithmetic::Double;
+        frustal.sync_options(&options);
+        frustal.change_domain(x, y, scale);
+        frustal
+    }
+
+    fn fresh(frustal: &Frustal) -> Frustal {
+        let domain = frustal.current_domain();
+        let mut fresh = exact(&domain.x, &domain.y, &domain.scale);
+        fresh.render();
+        fresh
+    }
+
+    #[test]
+    fn shift_keeps_computed_pixels() {
+        let mut shifted = exact("-0.5", "0", "1");
+        shifted.render();
+        shifted.shift_domain(Point::new(5., -3.));
+        assert!(shifted.results.iter().any(Option::is_none));
+        assert!(shifted.results.iter().any(Option::is_some));
+        shifted.render();
+        assert_same(&shifted, &fresh(&shifted));
+    }
+
+    #[test]
+    fn exact_scale_keeps_computed_pixels() {
+        for ratio in [2., 0.5] {
+            let mut scaled = exact("-0.5", "0", "1");
+            scaled.render();
+            scaled.scale_domain(ratio, Point::new(20.5, 12.5));
+            assert!(scaled.results.iter().any(Option::is_none));
+            assert!(scaled.results.iter().any(Option::is_some));
+            scaled.render();
+            assert_same(&scaled, &fresh(&scaled));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "tile buffer size")]
+    fn render_tile_into_rejects_wrong_buffers() {
+        let mut pixels: Vec<Pixel> = (0..15).map(|_| Pixel::void()).collect();
+        frustal(|_| ()).render_tile_into(0, 0, 4, 4, &mut pixels);
     }
 }
@@ -0,0 +1,111 @@
+use crate::fractals::{IterationsMaybe, Options};
+use crate::pixel::Pixel;
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let norm = dot(v, v).sqrt();
+    [v[0] / norm, v[1] / norm, v[2] / norm]
+}
+
+// Slope between two neighbours, one sided on borders and next to
+// missing values (interior or not yet computed pixels)
+fn slope(before: Option<f64>, at: f64, after: Option<f64>) -> f64 {
+    match (before, after) {
+        (Some(before), Some(after)) => (after - before) / 2.,
+        (Some(before), None) => at - before,
+        (None, Some(after)) => after - at,
+        (None, None) => 0.,
+    }
+}
+
+// Surface normal of the smooth iteration count height field
+fn normal(
+    results: &[Option<IterationsMaybe>],
+    width: usize,
+    index: usize,
+    height: f64,
+) -> [f64; 3] {
+    let n = |i: usize| results.get(i).copied().flatten().flatten().map(|it| it.n);
+    let at = match n(index) {
+        Some(at) => at,
+        None => return [0., 0., 1.],
+    };
+    let x = index % width;
+    let left = if x > 0 { n(index - 1) } else { None };
+    let right = if x + 1 < width { n(index + 1) } else { None };
+    let up = if index >= width {
+        n(index - width)
+    } else {
+        None
+    };
+    let down = n(index + width);
+    // Screen y goes down
+    normalize([
+        -height * slope(left, at, right),
+        height * slope(up, at, down),
+        1.,
+    ])
+}
+
+// Blinn-Phong shading of the pixel at index over its base colour, the
+// light direction is given in degrees
+pub fn shade(
+    pixel: Pixel,
+    results: &[Option<IterationsMaybe>],
+    width: usize,
+    index: usize,
+    options: &Options,
+) -> Pixel {
+    let normal = normal(results, width, index, options.light_height);
+    let (azimuth, elevation) = (
+        options.light_azimuth.to_radians(),
+        options.light_elevation.to_radians(),
+    );
+    let light = [
+        elevation.cos() * azimuth.cos(),
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+    ];
+    let half = normalize([light[0], light[1], light[2] + 1.]);
+
+    let diffuse = dot(normal, light).max(0.);
+    let specular = dot(normal, half).max(0.).powf(options.shininess);
+    let intensity = options.ambient + options.diffuse * diffuse;
+    let highlight = 255. * options.specular * specular;
+    Pixel::from_f64(
+        f64::from(pixel.r) * intensity + highlight,
+        f64::from(pixel.g) * intensity + highlight,
+        f64::from(pixel.b) * intensity + highlight,
+    )
+}
+
+// Whether a neighbour converged elsewhere, darkening the basin boundaries
+// of Newton fractals
+pub fn on_boundary(results: &[Option<IterationsMaybe>], width: usize, index: usize) -> bool {
+    let channel = |i: usize| {
+        results
+            .get(i)
+            .copied()
+            .flatten()
+            .map(|r| r.map(|it| it.channel))
+    };
+    let at = channel(index);
+    let x = index % width;
+    let mut neighbours = vec![index + width];
+    if x > 0 {
+        neighbours.push(index - 1);
+    }
+    if x + 1 < width {
+        neighbours.push(index + 1);
+    }
+    if index >= width {
+        neighbours.push(index - width);
+    }
+    neighbours
+        .into_iter()
+        .filter_map(channel)
+        .any(|neighbour| Some(neighbour) != at)
+}
@@ -0,0 +1,258 @@
+use color_processing::Color;
+use std::fmt;
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+use crate::colorspace::ColorSpace;
+use crate::pixel::Pixel;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    Smooth,
+    Cubic,
+}
+
+// What happens to positions outside of [0, 1]
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Stop {
+    position: f64,
+    color: [f64; 3],
+}
+
+#[derive(Debug)]
+pub struct ParsePaletteError;
+
+// Gradient through colour stops placed in [0, 1]
+// The colouring position is mapped to offset + density * position before
+// being wrapped into the gradient
+#[wasm_bindgen]
+#[derive(Clone, PartialEq)]
+pub struct Palette {
+    stops: Vec<Stop>,
+    pub interpolation: Interpolation,
+    // Space in which stops are interpolated
+    pub space: ColorSpace,
+    pub wrap: Wrap,
+    pub offset: f64,
+    pub density: f64,
+}
+
+fn lerp(a: [f64; 3], b: [f64; 3], t: f64) -> [f64; 3] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+// Catmull-Rom spline between b and c
+fn cubic(a: [f64; 3], b: [f64; 3], c: [f64; 3], d: [f64; 3], t: f64) -> [f64; 3] {
+    let mut color = [0.; 3];
+    for i in 0..3 {
+        color[i] = b[i]
+            + 0.5
+                * t
+                * (c[i] - a[i]
+                    + t * (2. * a[i] - 5. * b[i] + 4. * c[i] - d[i]
+                        + t * (3. * (b[i] - c[i]) + d[i] - a[i])));
+    }
+    color
+}
+
+#[wasm_bindgen]
+impl Palette {
+    pub fn new(interpolation: Interpolation, wrap: Wrap) -> Palette {
+        Palette {
+            stops: Vec::new(),
+            interpolation,
+            space: ColorSpace::Rgb,
+            wrap,
+            offset: 0.,
+            density: 1.,
+        }
+    }
+
+    // Returns undefined when the description is invalid
+    pub fn parse(description: &str) -> Option<Palette> {
+        description.parse().ok()
+    }
+
+    pub fn add_stop(&mut self, position: f64, r: u8, g: u8, b: u8) {
+        self.insert(
+            position,
+            [
+                f64::from(r) / 255.,
+                f64::from(g) / 255.,
+                f64::from(b) / 255.,
+            ],
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.stops.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.stops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stops.is_empty()
+    }
+
+    pub fn description(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Palette {
+    // Components are in [0, 1]
+    pub fn insert(&mut self, position: f64, color: [f64; 3]) {
+        let index = self.stops.partition_point(|stop| stop.position <= position);
+        self.stops.insert(index, Stop { position, color });
+    }
+
+    fn wrap(&self, position: f64) -> f64 {
+        match self.wrap {
+            Wrap::Repeat => position - position.floor(),
+            Wrap::Mirror => {
+                let position = position.rem_euclid(2.);
+                if position > 1. {
+                    2. - position
+                } else {
+                    position
+                }
+            }
+            Wrap::Clamp => position.clamp(0., 1.),
+        }
+    }
+
+    // Stops extended periodically when repeating, and clamped otherwise
+    fn stop(&self, index: isize) -> Stop {
+        let len = self.stops.len() as isize;
+        if self.wrap == Wrap::Repeat {
+            let stop = self.stops[index.rem_euclid(len) as usize];
+            Stop {
+                position: stop.position + index.div_euclid(len) as f64,
+                color: stop.color,
+            }
+        } else {
+            self.stops[index.clamp(0, len - 1) as usize]
+        }
+    }
+
+    // Components are in [0, 1]
+    pub fn color_at(&self, position: f64) -> [f64; 3] {
+        self.gradient(self.offset + self.density * position)
+    }
+
+    pub(crate) fn gradient(&self, t: f64) -> [f64; 3] {
+        if self.stops.is_empty() {
+            return [0.; 3];
+        }
+        let t = self.wrap(t);
+        let index = self.stops.partition_point(|stop| stop.position <= t) as isize - 1;
+        let (start, end) = (self.stop(index), self.stop(index + 1));
+        let width = end.position - start.position;
+        if width <= 0. {
+            return end.color;
+        }
+        let t = (t - start.position) / width;
+        if self.interpolation == Interpolation::Step {
+            return start.color;
+        }
+        let mut colors = [
+            self.stop(index - 1).color,
+            start.color,
+            end.color,
+            self.stop(index + 2).color,
+        ]
+        .map(|color| self.space.from_rgb(color));
+        self.space.unwrap_hues(&mut colors);
+        let [before, start, end, after] = colors;
+        self.space.to_rgb(match self.interpolation {
+            Interpolation::Step | Interpolation::Linear => lerp(start, end, t),
+            Interpolation::Smooth => lerp(start, end, t * t * (3. - 2. * t)),
+            Interpolation::Cubic => cubic(before, start, end, after, t),
+        })
+    }
+
+    // Blends the gradient colour with white and black like hwb does with
+    // its hue, shift moves along the gradient independently of density
+    pub fn get_pixel(&self, position: f64, shift: f64, whiteness: f64, blackness: f64) -> Pixel {
+        Pixel::from_rgb_mixed(
+            self.gradient(self.offset + shift + self.density * position),
+            whiteness,
+            blackness,
+        )
+    }
+}
+
+// Stops are written as `color position` separated by `;`, colors being any
+// css color. Positions can be omitted to spread stops evenly:
+// #000764 0; #206bcb .16; #edffff .42; #ffaa00 .6425; #000200 .8575
+impl FromStr for Palette {
+    type Err = ParsePaletteError;
+
+    fn from_str(s: &str) -> Result<Palette, ParsePaletteError> {
+        let stops: Vec<&str> = s
+            .split(';')
+            .map(str::trim)
+            .filter(|stop| !stop.is_empty())
+            .collect();
+        if stops.is_empty() {
+            return Err(ParsePaletteError);
+        }
+        let mut palette = Palette::new(Interpolation::Linear, Wrap::Repeat);
+        for (i, stop) in stops.iter().enumerate() {
+            let (color, position) = match stop.rsplit_once(char::is_whitespace) {
+                Some((color, position)) if position.parse::<f64>().is_ok() => {
+                    (color.trim(), position.parse().unwrap())
+                }
+                _ => (*stop, i as f64 / stops.len() as f64),
+            };
+            let color = Color::new_string(color).ok_or(ParsePaletteError)?;
+            palette.insert(
+                position,
+                [
+                    f64::from(color.red) / 255.,
+                    f64::from(color.green) / 255.,
+                    f64::from(color.blue) / 255.,
+                ],
+            );
+        }
+        Ok(palette)
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, stop) in self.stops.iter().enumerate() {
+            let [r, g, b] = stop.color;
+            let channel = |c: f64| (c * 255.).round() as u8;
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(
+                f,
+                "#{:02x}{:02x}{:02x} {}",
+                channel(r),
+                channel(g),
+                channel(b),
+                stop.position
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,280 @@
+use std::f64::consts::PI;
+use wasm_bindgen::prelude::*;
+
+use crate::colorspace::ColorSpace;
+use crate::palette::{Interpolation, Palette, Wrap};
+
+// Number of stops sampled in gimp segments that aren't linear in rgb
+const SEGMENT_SAMPLES: usize = 16;
+// Ultra Fractal gradients have 400 positions
+const UGR_POSITIONS: f64 = 400.;
+const MAP_COLORS: usize = 256;
+const EPSILON: f64 = 1e-10;
+
+// Segment of a gimp gradient, positions in [0, 1]
+struct Segment {
+    left: f64,
+    middle: f64,
+    right: f64,
+    left_color: [f64; 3],
+    right_color: [f64; 3],
+    blending: usize,
+    coloring: usize,
+}
+
+impl Segment {
+    fn parse(line: &str) -> Option<Segment> {
+        let values = line
+            .split_whitespace()
+            .map(|value| value.parse::<f64>().ok())
+            .collect::<Option<Vec<f64>>>()?;
+        if values.len() < 12 {
+            return None;
+        }
+        Some(Segment {
+            left: values[0],
+            middle: values[1],
+            right: values[2],
+            left_color: [values[3], values[4], values[5]],
+            right_color: [values[7], values[8], values[9]],
+            blending: values[11] as usize,
+            coloring: values.get(12).map_or(0, |&coloring| coloring as usize),
+        })
+    }
+
+    // Same blending functions as gimp, position and middle being relative
+    // to the segment
+    fn factor(&self, position: f64) -> f64 {
+        let width = self.right - self.left;
+        if width < EPSILON {
+            return 0.;
+        }
+        let position = (position - self.left) / width;
+        let middle = (self.middle - self.left) / width;
+        let linear = if position <= middle {
+            if middle < EPSILON {
+                0.
+            } else {
+                0.5 * position / middle
+            }
+        } else if 1. - middle < EPSILON {
+            1.
+        } else {
+            0.5 + 0.5 * (position - middle) / (1. - middle)
+        };
+        match self.blending {
+            1 => position.powf(0.5_f64.ln() / middle.max(EPSILON).ln()),
+            2 => ((-PI / 2. + PI * linear).sin() + 1.) / 2.,
+            3 => (1. - (linear - 1.).powi(2)).sqrt(),
+            4 => 1. - (1. - linear.powi(2)).sqrt(),
+            5 => {
+                if position >= middle {
+                    1.
+                } else {
+                    0.
+                }
+            }
+            _ => linear,
+        }
+    }
+
+    fn color_at(&self, position: f64) -> [f64; 3] {
+        let factor = self.factor(position);
+        if self.coloring == 0 {
+            let [l, r] = [self.left_color, self.right_color];
+            return [
+                l[0] + (r[0] - l[0]) * factor,
+                l[1] + (r[1] - l[1]) * factor,
+                l[2] + (r[2] - l[2]) * factor,
+            ];
+        }
+        let left = ColorSpace::Hsv.from_rgb(self.left_color);
+        let right = ColorSpace::Hsv.from_rgb(self.right_color);
+        // 1 turns counter-clockwise and 2 clockwise around the hue circle
+        let hue = if self.coloring == 1 {
+            let turn = if left[0] < right[0] {
+                right[0] - left[0]
+            } else {
+                360. - (left[0] - right[0])
+            };
+            left[0] + turn * factor
+        } else {
+            let turn = if right[0] < left[0] {
+                left[0] - right[0]
+            } else {
+                360. - (right[0] - left[0])
+            };
+            left[0] - turn * factor
+        };
+        ColorSpace::Hsv.to_rgb([
+            hue,
+            left[1] + (right[1] - left[1]) * factor,
+            left[2] + (right[2] - left[2]) * factor,
+        ])
+    }
+}
+
+#[wasm_bindgen]
+impl Palette {
+    // Gimp gradient (.ggr), linear rgb segments map exactly to stops,
+    // other segments are sampled
+    pub fn from_ggr(text: &str) -> Option<Palette> {
+        let mut lines = text.lines().map(str::trim);
+        if lines.next()? != "GIMP Gradient" {
+            return None;
+        }
+        let mut lines = lines.filter(|line| !line.is_empty() && !line.starts_with("Name:"));
+        let count: usize = lines.next()?.parse().ok()?;
+        let segments = lines
+            .take(count)
+            .map(Segment::parse)
+            .collect::<Option<Vec<Segment>>>()?;
+        if segments.len() != count || count == 0 {
+            return None;
+        }
+
+        let mut palette = Palette::new(Interpolation::Linear, Wrap::Repeat);
+        for segment in &segments {
+            if segment.blending == 0 && segment.coloring == 0 {
+                for position in &[segment.left, segment.middle, segment.right] {
+                    palette.insert(*position, segment.color_at(*position));
+                }
+            } else {
+                for i in 0..=SEGMENT_SAMPLES {
+                    let position = segment.left
+                        + (segment.right - segment.left) * i as f64 / SEGMENT_SAMPLES as f64;
+                    palette.insert(position, segment.color_at(position));
+                }
+            }
+        }
+        Some(palette)
+    }
+
+    // Fractint map (.map): one `r g b` line per colour, evenly spread
+    pub fn from_map(text: &str) -> Option<Palette> {
+        let colors: Vec<[f64; 3]> = text
+            .lines()
+            .filter_map(|line| {
+                let mut values = line
+                    .split_whitespace()
+                    .map(|value| value.parse::<u8>().ok());
+                let mut component = || Some(f64::from(values.next()??) / 255.);
+                Some([component()?, component()?, component()?])
+            })
+            .collect();
+        if colors.is_empty() {
+            return None;
+        }
+
+        let mut palette = Palette::new(Interpolation::Linear, Wrap::Repeat);
+        for (i, color) in colors.iter().enumerate() {
+            palette.insert(i as f64 / colors.len() as f64, *color);
+        }
+        Some(palette)
+    }
+
+    // First gradient of an Ultra Fractal gradient file (.ugr)
+    pub fn from_ugr(text: &str) -> Option<Palette> {
+        let start = text.find("gradient:")? + "gradient:".len();
+        let section = &text[start..];
+        // The gradient section ends with the opacity one or the entry
+        let end = ["opacity:", "}"]
+            .iter()
+            .filter_map(|end| section.find(end))
+            .min()
+            .unwrap_or(section.len());
+        let mut palette = Palette::new(Interpolation::Linear, Wrap::Repeat);
+        let mut index = None;
+        for token in section[..end].split_whitespace() {
+            match token.split_once('=') {
+                Some(("smooth", "yes")) => palette.interpolation = Interpolation::Cubic,
+                Some(("index", value)) => index = value.parse::<i64>().ok(),
+                Some(("color", value)) => {
+                    // Colors are stored as 0xBBGGRR
+                    let color = value.parse::<u32>().ok()?;
+                    let component = |shift: u32| f64::from((color >> shift) & 0xff) / 255.;
+                    let position = index?.rem_euclid(UGR_POSITIONS as i64) as f64 / UGR_POSITIONS;
+                    palette.insert(position, [component(0), component(8), component(16)]);
+                }
+                _ => (),
+            }
+        }
+        if palette.is_empty() {
+            None
+        } else {
+            Some(palette)
+        }
+    }
+
+    // Exports one cycle of the gradient as a 256 colours Fractint map
+    pub fn to_map(&self) -> String {
+        (0..MAP_COLORS)
+            .map(|i| {
+                let color = self.gradient(i as f64 / MAP_COLORS as f64);
+                let channel = |c: f64| (c * 255.).round() as u8;
+                format!(
+                    "{} {} {}\n",
+                    channel(color[0]),
+                    channel(color[1]),
+                    channel(color[2])
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color(actual: [f64; 3], expected: [f64; 3]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn parses_gimp_gradients() {
+        let text = "GIMP Gradient\n\
+                    Name: Red to blue\n\
+                    2\n\
+                    0 0.25 0.5 1 0 0 1 0 0 1 1 0 0\n\
+                    0.5 0.75 1 0 0 1 1 1 0 0 1 2 0\n";
+        let palette = Palette::from_ggr(text).unwrap();
+        assert_eq!(palette.len(), 3 + SEGMENT_SAMPLES + 1);
+        assert_color(palette.gradient(0.), [1., 0., 0.]);
+        assert_color(palette.gradient(0.25), [0.5, 0., 0.5]);
+        assert_color(palette.gradient(0.5), [0., 0., 1.]);
+        // Sine blending is halfway at the middle
+        assert_color(palette.gradient(0.75), [0.5, 0., 0.5]);
+        assert!(Palette::from_ggr("GIMP Gradient\n1\n0 0.5 1\n").is_none());
+        assert!(Palette::from_ggr("1\n0 0.5 1 0 0 0 1 1 1 1 1 0 0\n").is_none());
+    }
+
+    #[test]
+    fn parses_fractint_maps() {
+        let palette = Palette::from_map("255 0 0 red\n0 255 0\n\n0 0 255\n").unwrap();
+        assert_eq!(palette.len(), 3);
+        assert_color(palette.gradient(1. / 3.), [0., 1., 0.]);
+        assert_color(palette.gradient(5. / 6.), [0.5, 0., 0.5]);
+        assert!(Palette::from_map("not a map\n").is_none());
+        let exported = Palette::from_map(&palette.to_map()).unwrap();
+        assert_eq!(exported.len(), MAP_COLORS);
+        assert_eq!(exported.to_map(), palette.to_map());
+    }
+
+    #[test]
+    fn parses_ultra_fractal_gradients() {
+        let text = "Fire {\n\
+                    gradient:\n  title=\"Fire\" smooth=yes\n  \
+                    index=0 color=255\n  index=200 color=16711680\n\
+                    opacity:\n  smooth=no index=0 opacity=255\n\
+                    }\n";
+        let palette = Palette::from_ugr(text).unwrap();
+        assert_eq!(palette.len(), 2);
+        assert!(palette.interpolation == Interpolation::Cubic);
+        assert_color(palette.gradient(0.), [1., 0., 0.]);
+        assert_color(palette.gradient(0.5), [0., 0., 1.]);
+        assert!(Palette::from_ugr("Empty {\ngradient:\n}\n").is_none());
+    }
+}
@@ -0,0 +1,367 @@
+use num_complex::{Complex, Complex64};
+use num_traits::{One, Zero};
+
+use crate::bigfloat::{BigFloat, BigPoint};
+use crate::domain::Domain;
+use crate::floatexp::FloatExp;
+use crate::fractals::{
+    bailout, escape_smoothing, julia_smoothing, Accumulator, Iterations, IterationsMaybe, Options,
+    Variant,
+};
+use crate::kernel::FLOATEXP_SCALE;
+use crate::point::Point;
+use crate::scalar::Scalar;
+
+// Pixel orbit relative to the reference orbit
+struct Orbit<T> {
+    delta: Complex<T>,
+    delta_c: Complex<T>,
+    m: usize,
+    iterations: usize,
+    c: Complex64,
+    accumulator: Accumulator,
+}
+
+// Deep zoom rendering by perturbation:
+// A reference orbit Zn is computed once at the domain origin with arbitrary
+// precision, each pixel then only iterates its difference δn = zn - Zn
+// which stays representable in f64 (or FloatExp) whatever the zoom level.
+pub struct Perturbation {
+    variant: Variant,
+    order: i32,
+    orbit: Vec<Complex64>,
+    c: Complex64,
+}
+
+fn big_mul(a: &BigPoint, b: &BigPoint, precision: i64) -> BigPoint {
+    BigPoint::new(
+        (&(&a.x * &b.x) - &(&a.y * &b.y)).truncate(precision),
+        (&(&a.x * &b.y) + &(&a.y * &b.x)).truncate(precision),
+    )
+}
+
+fn binomial(n: i32, k: i32) -> f64 {
+    (0..k).fold(1., |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+impl Perturbation {
+    pub fn new(domain: &Domain, options: &Options) -> Option<Perturbation> {
+        let variant = options.variant;
+        match variant {
+            Variant::Mandelbrot | Variant::Mandelbar | Variant::Julia if options.order >= 2 => (),
+            _ => return None,
+        }
+        let precision = domain.precision();
+        let (mut z, c) = if variant == Variant::Julia {
+            (
+                domain.origin.clone(),
+                BigPoint::new(
+                    BigFloat::from_f64(options.const_real),
+                    BigFloat::from_f64(options.const_imaginary),
+                ),
+            )
+        } else {
+            (
+                BigPoint::new(BigFloat::zero(), BigFloat::zero()),
+                domain.origin.clone(),
+            )
+        };
+        let to_complex = |p: &BigPoint| Complex64::new(p.x.to_f64(), p.y.to_f64());
+
+        let mut orbit = Vec::with_capacity(options.precision + 1);
+        orbit.push(to_complex(&z));
+        while orbit.len() <= options.precision {
+            let base = if variant == Variant::Mandelbar {
+                BigPoint::new(z.x.clone(), -&z.y)
+            } else {
+                z.clone()
+            };
+            let mut power = base.clone();
+            for _ in 1..options.order {
+                power = big_mul(&power, &base, precision);
+            }
+            z = BigPoint::new(&power.x + &c.x, &power.y + &c.y);
+            let zn = to_complex(&z);
+            orbit.push(zn);
+            if zn.norm_sqr() > 4. {
+                break;
+            }
+        }
+
+        Some(Perturbation {
+            variant,
+            order: options.order,
+            orbit,
+            c: to_complex(&c),
+        })
+    }
+
+    // (Z + δ)^d - Z^d = Σ C(d, k) Z^(d-k) δ^k for k in 1..=d
+    fn step<T: Scalar>(&self, reference: Complex64, delta: Complex<T>) -> Complex<T> {
+        let (reference, delta) = if self.variant == Variant::Mandelbar {
+            (reference.conj(), delta.conj())
+        } else {
+            (reference, delta)
+        };
+        let reference = Complex::new(T::from_f64(reference.re), T::from_f64(reference.im));
+        if self.order == 2 {
+            // 2Zδ + δ²
+            return (reference + reference + delta) * delta;
+        }
+        let mut powers: Vec<Complex<T>> = vec![Complex::one()];
+        for k in 1..self.order as usize {
+            powers.push(powers[k - 1] * reference);
+        }
+        // Horner scheme in δ
+        let mut sum: Complex<T> = Complex::one();
+        for k in (1..self.order).rev() {
+            let coefficient = T::from_f64(binomial(self.order, k));
+            sum = sum * delta + powers[(self.order - k) as usize].scale(coefficient);
+        }
+        sum * delta
+    }
+
+    fn start<T: Scalar>(&self, position: Point, scale: T, options: &Options) -> Orbit<T> {
+        let offset = Complex::new(
+            T::from_f64(position.x) * scale,
+            T::from_f64(position.y) * scale,
+        );
+        if self.variant == Variant::Julia {
+            Orbit {
+                delta: offset,
+                delta_c: Complex::zero(),
+                m: 0,
+                iterations: 0,
+                c: self.c,
+                accumulator: Accumulator::new(options, self.c),
+            }
+        } else {
+            let c = self.c + Complex64::new(offset.re.to_f64(), offset.im.to_f64());
+            Orbit {
+                delta: Complex::zero(),
+                delta_c: offset,
+                m: 0,
+                iterations: 0,
+                c,
+                accumulator: Accumulator::new(options, c),
+            }
+        }
+    }
+
+    // Returns None when δ grows over bound, leaving the orbit where it stopped
+    fn iterate<T: Scalar>(
+        &self,
+        orbit: &mut Orbit<T>,
+        options: &Options,
+        bound: Option<T>,
+    ) -> Option<IterationsMaybe> {
+        let julia = self.variant == Variant::Julia;
+        let last = self.orbit.len() - 1;
+        let bailout = bailout(options);
+
+        while orbit.iterations < options.precision {
+            orbit.delta = self.step(self.orbit[orbit.m], orbit.delta) + orbit.delta_c;
+            orbit.m += 1;
+            let reference = self.orbit[orbit.m];
+            let z = reference + Complex64::new(orbit.delta.re.to_f64(), orbit.delta.im.to_f64());
+            let mod2 = z.norm_sqr();
+            orbit.accumulator.add(z);
+            if mod2 > bailout {
+                let mut n = orbit.iterations as f64;
+                if options.smooth {
+                    n -= if julia {
+                        julia_smoothing(mod2)
+                    } else {
+                        escape_smoothing(mod2, orbit.c.norm(), self.order)
+                    };
+                }
+                return Some(Some(Iterations {
+                    average: orbit.accumulator.finish(mod2, options),
+                    ..Iterations::all(n)
+                }));
+            }
+            orbit.iterations += 1;
+
+            if julia {
+                if orbit.m == last {
+                    // Reference is exhausted, finish with plain iterations
+                    return Some(self.finish_julia(z, orbit, options));
+                }
+            } else {
+                // Rebase on the start of the orbit (Z0 = 0) when the pixel
+                // gets closer to 0 than to the reference, or when the
+                // reference has escaped
+                let full = Complex::new(T::from_f64(reference.re), T::from_f64(reference.im))
+                    + orbit.delta;
+                if orbit.m == last || full.norm_sqr() < orbit.delta.norm_sqr() {
+                    orbit.delta = full;
+                    orbit.m = 0;
+                }
+            }
+            if let Some(bound) = bound {
+                if orbit.delta.norm_sqr() > bound {
+                    return None;
+                }
+            }
+        }
+        Some(None)
+    }
+
+    // position is the pixel offset from origin in scale units
+    pub fn get_iterations_at(
+        &self,
+        position: Point,
+        scale: f64,
+        options: &Options,
+    ) -> IterationsMaybe {
+        let mut orbit = self.start(position, scale, options);
+        self.iterate(&mut orbit, options, None).unwrap_or(None)
+    }
+
+    // FloatExp is only needed while δ is tiny, as soon as it fits in f64
+    // the orbit goes on with f64 arithmetic
+    pub fn get_iterations_at_extended(
+        &self,
+        position: Point,
+        scale: FloatExp,
+        options: &Options,
+    ) -> IterationsMaybe {
+        let mut orbit = self.start(position, scale, options);
+        let bound = FloatExp::from_f64(FLOATEXP_SCALE).powi(2);
+        if let Some(iterations) = self.iterate(&mut orbit, options, Some(bound)) {
+            return iterations;
+        }
+        let to_f64 = |z: Complex<FloatExp>| Complex64::new(z.re.to_f64(), z.im.to_f64());
+        let mut orbit = Orbit {
+            delta: to_f64(orbit.delta),
+            delta_c: to_f64(orbit.delta_c),
+            m: orbit.m,
+            iterations: orbit.iterations,
+            c: orbit.c,
+            accumulator: orbit.accumulator,
+        };
+        self.iterate(&mut orbit, options, None).unwrap_or(None)
+    }
+
+    fn finish_julia<T>(
+        &self,
+        mut z: Complex64,
+        orbit: &mut Orbit<T>,
+        options: &Options,
+    ) -> IterationsMaybe {
+        let bailout = bailout(options);
+        while orbit.iterations < options.precision {
+            z = z.powi(self.order) + self.c;
+            let mod2 = z.norm_sqr();
+            orbit.accumulator.add(z);
+            if mod2 > bailout {
+                let mut n = orbit.iterations as f64;
+                if options.smooth {
+                    n -= julia_smoothing(mod2);
+                }
+                return Some(Iterations {
+                    average: orbit.accumulator.finish(mod2, options),
+                    ..Iterations::all(n)
+                });
+            }
+            orbit.iterations += 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::{Arithmetic, Kernel};
+    use crate::Frustal;
+
+    // Around the Misiurewicz point i pixels escape after a number of
+    // iterations growing with the zoom depth, while the reference never does
+    fn domain(scale: FloatExp) -> Domain {
+        let mut domain = Domain::new(Point::new(32., 24.));
+        domain.change(
+            BigPoint::new(BigFloat::zero(), BigFloat::from_f64(1.)),
+            scale,
+        );
+        domain
+    }
+
+    fn options(precision: usize, arithmetic: Arithmetic) -> Options {
+        Options {
+            precision,
+            arithmetic,
+            ..Frustal::new(0, 0, 1).current_options()
+        }
+    }
+
+    fn positions(domain: &Domain) -> Vec<Point> {
+        (0..32 * 24)
+            .map(|i| domain.position(domain.point(i)))
+            .collect()
+    }
+
+    fn n(iterations: IterationsMaybe) -> f64 {
+        iterations.expect("pixel should escape").n
+    }
+
+    #[test]
+    fn matches_double_double() {
+        let scale = 1e-20;
+        let domain = domain(FloatExp::from_f64(scale));
+        let exact = options(500, Arithmetic::DoubleDouble);
+        let options = options(500, Arithmetic::Auto);
+        let perturbation = Perturbation::new(&domain, &options).unwrap();
+        let kernel = Kernel::new(None, &domain, &exact);
+        for position in positions(&domain) {
+            let perturbed = n(perturbation.get_iterations_at(position, scale, &options));
+            let expected = n(kernel.get_iterations_at(position, &exact));
+            assert!(
+                (perturbed - expected).abs() < 1e-6,
+                "{} != {}",
+                perturbed,
+                expected
+            );
+        }
+    }
+
+    // The extended orbits switch to f64 past FLOATEXP_SCALE and rebase
+    // once the pixel gets closer to 0 than to the reference
+    #[test]
+    fn extended_matches_f64() {
+        let scale = 1e-300;
+        let domain = domain(FloatExp::from_f64(scale));
+        let options = options(3000, Arithmetic::Auto);
+        let perturbation = Perturbation::new(&domain, &options).unwrap();
+        for position in positions(&domain) {
+            let perturbed = n(perturbation.get_iterations_at(position, scale, &options));
+            let extended = n(perturbation.get_iterations_at_extended(
+                position,
+                FloatExp::from_f64(scale),
+                &options,
+            ));
+            assert!(
+                (perturbed - extended).abs() < 1e-6,
+                "{} != {}",
+                perturbed,
+                extended
+            );
+        }
+    }
+
+    #[test]
+    fn renders_beyond_f64() {
+        let domain = domain("1e-400".parse().unwrap());
+        let options = options(4000, Arithmetic::Auto);
+        let perturbation = Perturbation::new(&domain, &options).unwrap();
+        let kernel = Kernel::new(Some(&perturbation), &domain, &options);
+        assert!(matches!(kernel, Kernel::FloatExpPerturbation { .. }));
+        let mut escaped: Vec<f64> = positions(&domain)
+            .into_iter()
+            .map(|position| n(kernel.get_iterations_at(position, &options)))
+            .collect();
+        escaped.sort_by(f64::total_cmp);
+        escaped.dedup();
+        assert!(escaped.len() > 100 && escaped[0] > 1000.);
+    }
+}
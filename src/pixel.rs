@@ -23,6 +23,24 @@ impl Pixel {
         }
     }
 
+    // Components in [0, 1]
+    pub fn from_rgb(rgb: [f64; 3]) -> Pixel {
+        Pixel::from_f64(255. * rgb[0], 255. * rgb[1], 255. * rgb[2])
+    }
+
+    // Mixes the colour with white and black the way hwb does with its hue
+    pub fn from_rgb_mixed(rgb: [f64; 3], whiteness: f64, blackness: f64) -> Pixel {
+        let mut w = whiteness.clamp(0., 1.);
+        let mut b = blackness.clamp(0., 1.);
+        if w + b > 1. {
+            let sum = w + b;
+            w /= sum;
+            b /= sum;
+        }
+        let channel = |c: f64| 255. * (c * (1. - w - b) + w);
+        Pixel::from_f64(channel(rgb[0]), channel(rgb[1]), channel(rgb[2]))
+    }
+
     pub fn from_color(color: Color) -> Pixel {
         Pixel {
             r: color.red,
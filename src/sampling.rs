@@ -0,0 +1,182 @@
+use wasm_bindgen::prelude::*;
+
+use crate::fractals::IterationsMaybe;
+use crate::pixel::Pixel;
+use crate::point::Point;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sampling {
+    // Regular grid of samples in every pixel
+    Grid,
+    // One random sample in every grid cell
+    Jittered,
+    // Grid in pixels whose colour differs from a neighbour
+    Adaptive,
+}
+
+// Reconstruction filter weighting the samples of a pixel, box only covers
+// the pixel while the others spread over its neighbours
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Filter {
+    Box,
+    Tent,
+    Gaussian,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub struct Supersampling {
+    pub sampling: Sampling,
+    // Samples along each axis
+    pub samples: usize,
+    pub filter: Filter,
+    // Largest colour difference with a neighbour, in [0, 1], left alone in
+    // adaptive sampling
+    pub threshold: f64,
+}
+
+// Resampling of a low resolution image to the canvas
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Upscale {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Clone, Copy)]
+pub struct Sample {
+    pub weight: f64,
+    pub iterations: IterationsMaybe,
+}
+
+// Cheap deterministic noise in [0, 1) so jittered frames can be colored again
+fn noise(seed: usize) -> f64 {
+    let mut x = seed as u64 ^ 0x9e37_79b9_7f4a_7c15;
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[wasm_bindgen]
+impl Supersampling {
+    pub fn new(sampling: Sampling, samples: usize, filter: Filter) -> Supersampling {
+        Supersampling {
+            sampling,
+            samples,
+            filter,
+            threshold: 0.1,
+        }
+    }
+}
+
+impl Supersampling {
+    fn radius(&self) -> f64 {
+        match self.filter {
+            Filter::Box => 0.5,
+            Filter::Tent | Filter::Gaussian => 1.,
+        }
+    }
+
+    fn weight(&self, offset: Point) -> f64 {
+        let radius = self.radius();
+        match self.filter {
+            Filter::Box => 1.,
+            Filter::Tent => (1. - offset.x.abs() / radius) * (1. - offset.y.abs() / radius),
+            Filter::Gaussian => (-(offset.x * offset.x + offset.y * offset.y) / 0.5).exp(),
+        }
+    }
+
+    // Sample offsets from the pixel point, in pixels, with their weight
+    pub fn offsets(&self, index: usize) -> Vec<(Point, f64)> {
+        let samples = self.samples.max(1);
+        let radius = self.radius();
+        let mut offsets = Vec::with_capacity(samples * samples);
+        for j in 0..samples {
+            for i in 0..samples {
+                let seed = (index * samples + j) * samples + i;
+                let (dx, dy) = if self.sampling == Sampling::Jittered {
+                    (noise(2 * seed), noise(2 * seed + 1))
+                } else {
+                    (0.5, 0.5)
+                };
+                let cell = |k: usize, d: f64| radius * (2. * (k as f64 + d) / samples as f64 - 1.);
+                let offset = Point::new(cell(i, dx), cell(j, dy));
+                offsets.push((offset, self.weight(offset)));
+            }
+        }
+        offsets
+    }
+
+    // Only pixels on colour edges are refined in adaptive sampling
+    pub fn needs_samples(&self, data: &[Pixel], width: usize, index: usize) -> bool {
+        if self.sampling != Sampling::Adaptive {
+            return true;
+        }
+        let difference = |other: &Pixel| {
+            let pixel = &data[index];
+            let channel = |a: u8, b: u8| f64::from(a.abs_diff(b)) / 255.;
+            channel(pixel.r, other.r)
+                .max(channel(pixel.g, other.g))
+                .max(channel(pixel.b, other.b))
+        };
+        let x = index % width;
+        let mut neighbours = vec![index + width];
+        if x > 0 {
+            neighbours.push(index - 1);
+        }
+        if x + 1 < width {
+            neighbours.push(index + 1);
+        }
+        if index >= width {
+            neighbours.push(index - width);
+        }
+        neighbours
+            .into_iter()
+            .filter_map(|neighbour| data.get(neighbour))
+            .any(|neighbour| difference(neighbour) > self.threshold)
+    }
+}
+
+impl Upscale {
+    // Colour of the image at a point in pixels, their centres being
+    // integral, clamped to its borders
+    pub fn sample(self, data: &[Pixel], width: usize, height: usize, point: Point) -> Pixel {
+        let x = point.x.max(0.).min((width - 1) as f64);
+        let y = point.y.max(0.).min((height - 1) as f64);
+        if self == Upscale::Nearest {
+            let pixel = &data[y.round() as usize * width + x.round() as usize];
+            return Pixel::new(pixel.r, pixel.g, pixel.b, pixel.a);
+        }
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (tx, ty) = (x - x0 as f64, y - y0 as f64);
+        let channel = |value: fn(&Pixel) -> u8| {
+            let at = |x: usize, y: usize| f64::from(value(&data[y * width + x]));
+            (at(x0, y0) * (1. - tx) + at(x1, y0) * tx) * (1. - ty)
+                + (at(x0, y1) * (1. - tx) + at(x1, y1) * tx) * ty
+        };
+        Pixel::from_f64(
+            channel(|pixel| pixel.r),
+            channel(|pixel| pixel.g),
+            channel(|pixel| pixel.b),
+        )
+    }
+}
+
+// Weighted mean of the sample colours
+pub fn reconstruct(samples: &[Sample], color: impl Fn(IterationsMaybe) -> Pixel) -> Pixel {
+    let (mut r, mut g, mut b, mut total) = (0., 0., 0., 0.);
+    for sample in samples {
+        let pixel = color(sample.iterations);
+        r += sample.weight * f64::from(pixel.r);
+        g += sample.weight * f64::from(pixel.g);
+        b += sample.weight * f64::from(pixel.b);
+        total += sample.weight;
+    }
+    Pixel::from_f64(r / total, g / total, b / total)
+}
@@ -0,0 +1,99 @@
+use num_traits::Num;
+use std::fmt::Debug;
+use std::ops::Neg;
+
+use crate::bigfloat::BigFloat;
+use crate::doubledouble::DoubleDouble;
+use crate::floatexp::FloatExp;
+
+// Real number type the iteration kernels can be instantiated with
+pub trait Scalar: Num + Copy + Neg<Output = Self> + PartialOrd + Debug + Send + Sync {
+    fn from_f64(value: f64) -> Self;
+    fn from_floatexp(value: FloatExp) -> Self;
+    fn to_f64(self) -> f64;
+    fn abs(self) -> Self;
+
+    fn from_bigfloat(value: &BigFloat) -> Self {
+        Self::from_f64(value.to_f64())
+    }
+}
+
+impl Scalar for f64 {
+    fn from_f64(value: f64) -> f64 {
+        value
+    }
+
+    fn from_floatexp(value: FloatExp) -> f64 {
+        value.to_f64()
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn abs(self) -> f64 {
+        f64::abs(self)
+    }
+}
+
+impl Scalar for f32 {
+    fn from_f64(value: f64) -> f32 {
+        value as f32
+    }
+
+    fn from_floatexp(value: FloatExp) -> f32 {
+        value.to_f64() as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        f64::from(self)
+    }
+
+    fn abs(self) -> f32 {
+        f32::abs(self)
+    }
+}
+
+impl Scalar for FloatExp {
+    fn from_f64(value: f64) -> FloatExp {
+        FloatExp::from_f64(value)
+    }
+
+    fn from_floatexp(value: FloatExp) -> FloatExp {
+        value
+    }
+
+    fn to_f64(self) -> f64 {
+        FloatExp::to_f64(self)
+    }
+
+    fn abs(self) -> FloatExp {
+        FloatExp::abs(self)
+    }
+
+    fn from_bigfloat(value: &BigFloat) -> FloatExp {
+        value.to_floatexp()
+    }
+}
+
+impl Scalar for DoubleDouble {
+    fn from_f64(value: f64) -> DoubleDouble {
+        DoubleDouble::from_f64(value)
+    }
+
+    fn from_floatexp(value: FloatExp) -> DoubleDouble {
+        DoubleDouble::from_f64(value.to_f64())
+    }
+
+    fn to_f64(self) -> f64 {
+        DoubleDouble::to_f64(self)
+    }
+
+    fn abs(self) -> DoubleDouble {
+        DoubleDouble::abs(self)
+    }
+
+    fn from_bigfloat(value: &BigFloat) -> DoubleDouble {
+        DoubleDouble::from_bigfloat(value)
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::VecDeque;
+use wasm_bindgen::prelude::*;
+
+use crate::domain::Domain;
+use crate::fractals::{Iterations, IterationsMaybe, Options};
+use crate::kernel::Kernel;
+
+// How a full render chooses the pixels it iterates. Pixels the other
+// strategies fill get their smooth part and Newton distance interpolated,
+// so with smoothing they only approximate the pixel strategy
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Strategy {
+    // Every pixel is iterated
+    Pixels,
+    // Mariani-Silver: rectangles whose border and centre lie in a single
+    // iteration band are filled from the border, others are split in four
+    Subdivision,
+    // Follows the edges between iteration bands from a grid of lines and
+    // fills the regions they enclose, exact without smoothing nor averages
+    // but for islands missed by the grid
+    BoundaryTracing,
+}
+
+// Rectangles thinner than this are iterated pixel by pixel
+const MIN_SIZE: usize = 6;
+// Spacing of the lines edges are traced from, islands missed by tracing
+// can't be larger
+const TRACE_GRID: usize = 16;
+
+// Results that can be told apart only by their smooth part, averages being
+// too irregular to be interpolated
+fn same_band(a: IterationsMaybe, b: IterationsMaybe) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => {
+            a.average.is_none()
+                && b.average.is_none()
+                && a.channel == b.channel
+                && a.n.floor() == b.n.floor()
+        }
+        _ => false,
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn lerp_iterations(a: IterationsMaybe, b: IterationsMaybe, t: f64) -> IterationsMaybe {
+    let (a, b) = (a?, b?);
+    Some(Iterations {
+        n: lerp(a.n, b.n, t),
+        distance: a.distance.zip(b.distance).map(|(a, b)| lerp(a, b, t)),
+        ..a
+    })
+}
+
+type Rectangle = (usize, usize, usize, usize);
+
+pub enum Task {
+    Subdivide(Rectangle),
+    // Uniform rectangle whose interior is filled from the row
+    Fill(Rectangle, usize),
+}
+
+// Work left to a strategy render, kept between budgeted calls
+pub enum Work {
+    // Rectangles still to be subdivided or filled, borders included
+    Rectangles(Vec<Task>),
+    // Pixels to visit around band edges, and the ones queued so far
+    Edges {
+        queue: VecDeque<usize>,
+        queued: Vec<bool>,
+    },
+    // Next row whose regions left are filled
+    Runs(usize),
+}
+
+impl Work {
+    // Results already computed are kept, None for the pixels strategy or an
+    // empty frame
+    pub fn new(strategy: Strategy, domain: &Domain) -> Option<Work> {
+        let width = domain.size.x as usize;
+        let height = domain.size.y as usize;
+        if strategy == Strategy::Pixels || width == 0 || height == 0 {
+            return None;
+        }
+        Some(match strategy {
+            Strategy::Subdivision => {
+                Work::Rectangles(vec![Task::Subdivide((0, 0, width - 1, height - 1))])
+            }
+            _ => {
+                let mut queue = VecDeque::new();
+                let mut queued = vec![false; width * height];
+                for y in 0..height {
+                    for x in 0..width {
+                        if x % TRACE_GRID == 0
+                            || y % TRACE_GRID == 0
+                            || x == width - 1
+                            || y == height - 1
+                        {
+                            push(y * width + x, &mut queue, &mut queued);
+                        }
+                    }
+                }
+                Work::Edges { queue, queued }
+            }
+        })
+    }
+}
+
+fn push(index: usize, queue: &mut VecDeque<usize>, queued: &mut [bool]) {
+    if !queued[index] {
+        queued[index] = true;
+        queue.push_back(index);
+    }
+}
+
+// Results computed on demand
+struct Frame<'a> {
+    kernel: &'a Kernel<'a>,
+    domain: &'a Domain,
+    options: &'a Options,
+    results: &'a mut [Option<IterationsMaybe>],
+    // Indices of the results set
+    touched: &'a mut Vec<usize>,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Frame<'a> {
+    fn get(&mut self, x: usize, y: usize) -> IterationsMaybe {
+        self.get_index(y * self.width + x)
+    }
+
+    fn get_index(&mut self, index: usize) -> IterationsMaybe {
+        if let Some(result) = self.results[index] {
+            return result;
+        }
+        let result = self
+            .kernel
+            .get_iterations_at(self.domain.position(self.domain.point(index)), self.options);
+        self.set(index, result);
+        result
+    }
+
+    fn set(&mut self, index: usize, result: IterationsMaybe) {
+        self.results[index] = Some(result);
+        self.touched.push(index);
+    }
+
+    // Borders are included, the tasks left are pushed
+    fn subdivide(&mut self, (x0, y0, x1, y1): Rectangle, tasks: &mut Vec<Task>) {
+        if x1 - x0 < MIN_SIZE || y1 - y0 < MIN_SIZE {
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    self.get(x, y);
+                }
+            }
+            return;
+        }
+        let mut border = Vec::with_capacity(2 * (x1 - x0 + y1 - y0));
+        for x in x0..=x1 {
+            border.push((x, y0));
+            border.push((x, y1));
+        }
+        for y in y0 + 1..y1 {
+            border.push((x0, y));
+            border.push((x1, y));
+        }
+        // Escape bands are rings, a border lying in one can surround the
+        // set, which the centre most likely reveals
+        border.push(((x0 + x1) / 2, (y0 + y1) / 2));
+        let first = self.get(x0, y0);
+        let mut uniform = true;
+        for (x, y) in border {
+            uniform &= same_band(first, self.get(x, y));
+        }
+
+        if uniform {
+            tasks.push(Task::Fill((x0, y0, x1, y1), y0 + 1));
+            return;
+        }
+        let (xm, ym) = ((x0 + x1) / 2, (y0 + y1) / 2);
+        tasks.push(Task::Subdivide((xm, ym, x1, y1)));
+        tasks.push(Task::Subdivide((x0, ym, xm, y1)));
+        tasks.push(Task::Subdivide((xm, y0, x1, ym)));
+        tasks.push(Task::Subdivide((x0, y0, xm, ym)));
+    }
+
+    // Fills a row of the interior of a uniform rectangle
+    fn fill(&mut self, (x0, y0, x1, y1): Rectangle, y: usize) {
+        let ty = (y - y0) as f64 / (y1 - y0) as f64;
+        for x in x0 + 1..x1 {
+            let index = y * self.width + x;
+            if self.results[index].is_some() {
+                continue;
+            }
+            let tx = (x - x0) as f64 / (x1 - x0) as f64;
+            let horizontal = lerp_iterations(self.get(x0, y), self.get(x1, y), tx);
+            let vertical = lerp_iterations(self.get(x, y0), self.get(x, y1), ty);
+            // Mean of the interpolations between opposite borders
+            self.set(index, lerp_iterations(horizontal, vertical, 0.5));
+        }
+    }
+
+    // Pushes the neighbours of both sides of the band edges around index
+    fn edges(&mut self, index: usize, queue: &mut VecDeque<usize>, queued: &mut [bool]) {
+        let (width, height) = (self.width, self.height);
+        let result = self.get_index(index);
+        let (x, y) = (index % width, index / width);
+        let mut sides = vec![];
+        if x > 0 {
+            sides.push(index - 1);
+        }
+        if x + 1 < width {
+            sides.push(index + 1);
+        }
+        if y > 0 {
+            sides.push(index - width);
+        }
+        if y + 1 < height {
+            sides.push(index + width);
+        }
+        let around = |index: usize| {
+            let (x, y) = ((index % width) as isize, (index / width) as isize);
+            (-1..=1)
+                .flat_map(move |dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+                .filter(move |&(x, y)| {
+                    x >= 0 && y >= 0 && x < width as isize && y < height as isize
+                })
+                .map(move |(x, y)| y as usize * width + x as usize)
+        };
+        // Both sides of an edge are explored
+        for side in sides {
+            if let Some(other) = self.results[side] {
+                if !same_band(result, other) {
+                    for neighbour in around(index).chain(around(side)) {
+                        push(neighbour, queue, queued);
+                    }
+                }
+            }
+        }
+    }
+
+    // Every region left in the row is enclosed by computed pixels of its band
+    fn runs(&mut self, y: usize) {
+        let row = y * self.width;
+        let mut x = 0;
+        while x < self.width {
+            if self.results[row + x].is_some() {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while self.results[row + x].is_none() {
+                x += 1;
+            }
+            let (left, right) = (self.get(start - 1, y), self.get(x, y));
+            for fill in start..x {
+                let t = (fill - start + 1) as f64 / (x - start + 1) as f64;
+                self.set(row + fill, lerp_iterations(left, right, t));
+            }
+        }
+    }
+}
+
+// Carries on the work until it's done, giving None, or until stop tells
+// so from the indices of the results set, pushed to touched, giving the
+// work left.
+// Subdivision misses features lying entirely inside a rectangle away from
+// its centre. Without smoothing nor averages boundary tracing gives the
+// results of the pixel strategy, except for islands of a band lying
+// entirely inside another band between the grid lines. Smooth values of
+// the pixels filled are interpolated along their row
+pub fn resume(
+    work: Work,
+    kernel: &Kernel,
+    domain: &Domain,
+    options: &Options,
+    results: &mut [Option<IterationsMaybe>],
+    touched: &mut Vec<usize>,
+    stop: impl Fn(&[usize]) -> bool,
+) -> Option<Work> {
+    let mut frame = Frame {
+        kernel,
+        domain,
+        options,
+        results,
+        touched,
+        width: domain.size.x as usize,
+        height: domain.size.y as usize,
+    };
+    let mut work = work;
+    loop {
+        work = match work {
+            Work::Rectangles(mut tasks) => {
+                while let Some(task) = tasks.pop() {
+                    match task {
+                        Task::Subdivide(rectangle) => frame.subdivide(rectangle, &mut tasks),
+                        Task::Fill(rectangle, y) => {
+                            frame.fill(rectangle, y);
+                            if y + 1 < rectangle.3 {
+                                tasks.push(Task::Fill(rectangle, y + 1));
+                            }
+                        }
+                    }
+                    if !tasks.is_empty() && stop(frame.touched) {
+                        return Some(Work::Rectangles(tasks));
+                    }
+                }
+                return None;
+            }
+            Work::Edges {
+                mut queue,
+                mut queued,
+            } => {
+                let mut visited = 0;
+                while let Some(index) = queue.pop_front() {
+                    frame.edges(index, &mut queue, &mut queued);
+                    visited += 1;
+                    // Stop is asked once in a while, pixels are fast
+                    if visited % 64 == 0 && !queue.is_empty() && stop(frame.touched) {
+                        return Some(Work::Edges { queue, queued });
+                    }
+                }
+                Work::Runs(0)
+            }
+            Work::Runs(row) => {
+                for y in row..frame.height {
+                    frame.runs(y);
+                    if y + 1 < frame.height && stop(frame.touched) {
+                        return Some(Work::Runs(y + 1));
+                    }
+                }
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Frustal, Strategy, Variant};
+
+    // Whole iterations and channel of every pixel
+    fn bands(variant: Variant, strategy: Strategy) -> Vec<Option<(f64, usize)>> {
+        let mut frustal = Frustal::new(160, 120, 10);
+        let mut options = frustal.current_options();
+        options.variant = variant;
+        options.smooth = false;
+        options.precision = 200;
+        options.strategy = strategy;
+        options.const_real = -0.8;
+        options.const_imaginary = 0.156;
+        frustal.sync_options(&options);
+        frustal.change_domain("-0.5", "0", "1.5");
+        frustal.render();
+        frustal
+            .results
+            .iter()
+            .map(|result| result.flatten().map(|result| (result.n, result.channel)))
+            .collect()
+    }
+
+    #[test]
+    fn boundary_tracing_matches_pixels_without_smoothing() {
+        for variant in [Variant::Mandelbrot, Variant::Julia] {
+            assert!(
+                bands(variant, Strategy::BoundaryTracing) == bands(variant, Strategy::Pixels),
+                "{:?}",
+                variant as u32
+            );
+        }
+    }
+}
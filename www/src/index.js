@@ -1,6 +1,26 @@
 import { GUI } from "dat.gui";
 import debounce from "debounce";
-import { Frustal, Variant, Point, Colorization } from "frustals";
+import {
+  Frustal,
+  Variant,
+  Point,
+  Colorization,
+  Arithmetic,
+  Average,
+  ColorSpace,
+  RootShading,
+  Layer,
+  LayerSource,
+  Blend,
+  Supersampling,
+  Sampling,
+  Filter,
+  Strategy,
+  Upscale,
+  Palette,
+  Interpolation,
+  Wrap
+} from "frustals";
 
 import { memory } from "frustals/frustals_bg";
 import presets from "./presets";
@@ -19,21 +39,31 @@ const fractalLabels = {
   Newton7: "Newton 1/z⁶ + 8 / z³ - 1"
 };
 
+// Filled pixels get interpolated smooth values
+const strategyLabels = {
+  Subdivision: "Subdivision (approximate)",
+  BoundaryTracing: "Boundary tracing (approximate when smooth)"
+};
+
 const jsOptions = {
   preview: true,
-  previewScale: 10
+  previewScale: 10,
+  previewUpscale: Upscale.Bilinear,
+  // Milliseconds of rendering between two draws
+  renderBudget: 16,
+  // Palette cycles per second, 0 stops the animation
+  cycleSpeed: 0,
+  // Final renders only, interaction stays on single samples
+  antialias: false,
+  sampling: Sampling.Adaptive,
+  samples: 3,
+  filter: Filter.Tent,
+  threshold: 0.1
 };
 
 const mainCanvas = document.createElement("canvas");
-const previewCanvas = document.createElement("canvas");
 const { width, height } = document.body.getBoundingClientRect();
 
-previewCanvas.width = width / jsOptions.previewScale;
-previewCanvas.height = height / jsOptions.previewScale;
-previewCanvas.classList.add("frustal-canvas");
-previewCanvas.classList.add("frustal-preview");
-document.body.appendChild(previewCanvas);
-
 mainCanvas.width = width;
 mainCanvas.height = height;
 mainCanvas.classList.add("frustal-canvas");
@@ -61,43 +91,68 @@ const draw = (canvas, ptr) => {
     );
 };
 
-const render = async () => {
+// Reused renders keep the pixels scrolled by shift_domain or scaled by
+// scale_domain and only compute the missing ones
+const render = async ({ reused } = {}) => {
   if (!started) {
     return;
   }
   const id = ++renderId;
   let interactionDelay = 0;
+  if (reused) {
+    frustal.cancel();
+  } else {
+    frustal.reset_data();
+  }
   if (jsOptions.preview) {
-    mainCanvas
-      .getContext("2d")
-      .clearRect(0, 0, mainCanvas.width, mainCanvas.height);
+    // Upscaled into the pixels left to compute
     const t0 = performance.now();
     await frustal.preview_render();
+    frustal.upscale_preview(Number(jsOptions.previewUpscale));
     const t1 = performance.now();
     console.log(`Preview render : ${t1 - t0}ms. Drawn`);
-    draw(previewCanvas, frustal.preview_data_ptr());
     interactionDelay = t1 - t0 + 50;
   }
+  if (reused || jsOptions.preview) {
+    draw(mainCanvas, frustal.data_ptr());
+  }
   setTimeout(async () => {
     if (id !== renderId) {
       return;
     }
-    frustal.reset_data();
-    const splits = 16;
-    let i = 0;
-    while (i++ < splits) {
+    // Each slice fits in a frame, the main thread stays responsive whatever
+    // the variant and precision
+    let progress = 0;
+    while (progress < 1) {
       const t0 = performance.now();
-      await frustal.partial_render(splits, i - 1);
+      progress = frustal.render_for(jsOptions.renderBudget);
       const t1 = performance.now();
+      const percent = Math.round(progress * 100);
       if (id === renderId) {
-        console.log(`Render ${i}/${splits} : ${t1 - t0}ms. Drawn`);
+        console.log(`Render ${percent}% : ${t1 - t0}ms. Drawn`);
         draw(mainCanvas, frustal.data_ptr());
         await new Promise(resolve => setTimeout(resolve, 1));
       } else {
-        console.log(`Render ${i}/${splits} : ${t1 - t0}ms. Not drawn`);
+        console.log(`Render ${percent}% : ${t1 - t0}ms. Not drawn`);
         return;
       }
     }
+    if (jsOptions.antialias) {
+      const supersampling = Supersampling.new(
+        Number(jsOptions.sampling),
+        jsOptions.samples,
+        Number(jsOptions.filter)
+      );
+      supersampling.threshold = jsOptions.threshold;
+      const t0 = performance.now();
+      await frustal.supersample(supersampling);
+      supersampling.free();
+      const t1 = performance.now();
+      if (id === renderId) {
+        console.log(`Supersampling : ${t1 - t0}ms. Drawn`);
+        draw(mainCanvas, frustal.data_ptr());
+      }
+    }
   }, interactionDelay);
 };
 
@@ -107,8 +162,6 @@ window.addEventListener(
     const { width, height } = document.body.getBoundingClientRect();
     mainCanvas.width = width;
     mainCanvas.height = height;
-    previewCanvas.width = Math.floor(width / jsOptions.previewScale);
-    previewCanvas.height = Math.floor(height / jsOptions.previewScale);
     frustal.resize(width, height);
     render();
   }, 10),
@@ -167,7 +220,7 @@ mainCanvas.addEventListener(
         }
         frustal.shift_domain(Point.new(drag.x - clientX, drag.y - clientY));
         updateDomain();
-        render();
+        render({ reused: true });
         drag.x = clientX;
         drag.y = clientY;
       }, 1)),
@@ -233,7 +286,7 @@ mainCanvas.addEventListener(
           const [{ clientX, clientY }] = touches;
           frustal.shift_domain(Point.new(drag.x - clientX, drag.y - clientY));
           updateDomain();
-          render();
+          render({ reused: true });
           drag.x = clientX;
           drag.y = clientY;
         }
@@ -267,21 +320,55 @@ window.addEventListener(
   false
 );
 
+// Zooms by 2 around the centre of the clicked pixel, out with shift, keeping
+// the pixels landing on previous ones
+mainCanvas.addEventListener(
+  "dblclick",
+  ({ clientX, clientY, shiftKey }) => {
+    frustal.scale_domain(
+      shiftKey ? 2 : 0.5,
+      Point.new(Math.floor(clientX) + 0.5, Math.floor(clientY) + 0.5)
+    );
+    updateDomain();
+    render({ reused: true });
+  },
+  false
+);
+
+// Wheel deltas are gathered into whole zoom steps by two around a pixel
+// centre, so the pixels landing on previous ones are kept
+const wheelStep = 100;
+let wheelDelta = 0;
 mainCanvas.addEventListener(
   "wheel",
   ({ deltaY, clientX, clientY }) => {
-    const { height } = document.body.getBoundingClientRect();
-    frustal.zoom_domain(deltaY, Point.new(clientX, clientY));
+    wheelDelta += deltaY;
+    if (Math.abs(wheelDelta) < wheelStep) {
+      return;
+    }
+    frustal.scale_domain(
+      wheelDelta > 0 ? 2 : 0.5,
+      Point.new(Math.floor(clientX) + 0.5, Math.floor(clientY) + 0.5)
+    );
+    wheelDelta = 0;
     updateDomain();
-    render();
+    render({ reused: true });
   },
   false
 );
 const options = frustal.options;
 
+// Redraws the stored results after a colouring change
+const redraw = () => {
+  draw(mainCanvas, frustal.data_ptr());
+};
+
 const sync = debounce(() => {
-  frustal.sync_options(options);
-  render();
+  if (frustal.sync_options(options)) {
+    render();
+  } else {
+    redraw();
+  }
 }, 25);
 
 const gui = new GUI({
@@ -299,6 +386,17 @@ gui
   )
   .onChange(sync);
 gui.add(options, "precision", 2).onChange(sync);
+gui.add(options, "arithmetic", Arithmetic).onChange(sync);
+gui
+  .add(
+    options,
+    "strategy",
+    Object.entries(Strategy).reduce((acc, [name, index]) => {
+      acc[strategyLabels[name] || name] = index;
+      return acc;
+    }, {})
+  )
+  .onChange(sync);
 gui.add(options, "smooth").onChange(sync);
 gui.add(options, "order", 1, 15).onChange(sync);
 gui
@@ -309,6 +407,11 @@ gui
   .add(options, "const_imaginary", -1.0, 1.0)
   .step(0.01)
   .onChange(sync);
+gui.add(options, "average", Average).onChange(sync);
+gui
+  .add(options, "stripe_density", 1, 20)
+  .step(1)
+  .onChange(sync);
 gui.add(options, "colorization", Colorization).onChange(sync);
 gui
   .add(options, "black_threshold")
@@ -322,51 +425,62 @@ gui
   .add(options, "overexposure")
   .step(0.000001)
   .onChange(sync);
-gui.add(options, "color_base").onChange(sync);
+const colorBase = gui.add(options, "color_base").onChange(sync);
 gui.add(options, "color_rotation").onChange(sync);
+// Rgb and Oklab have no hue to rotate
+const hueSpaces = {
+  Hsl: ColorSpace.Hsl,
+  Hsv: ColorSpace.Hsv,
+  Hwb: ColorSpace.Hwb,
+  Lch: ColorSpace.Lch,
+  Oklch: ColorSpace.Oklch
+};
+gui.add(options, "color_space", hueSpaces).onChange(sync);
+gui.add(options, "root_shading", RootShading).onChange(sync);
+gui.add(options, "basin_boundary", 0, 1).onChange(sync);
+
+const lightingFolder = gui.addFolder("Lighting");
+lightingFolder.add(options, "lighting").onChange(sync);
+lightingFolder.add(options, "light_azimuth", 0, 360).onChange(sync);
+lightingFolder.add(options, "light_elevation", 0, 90).onChange(sync);
+lightingFolder
+  .add(options, "light_height")
+  .step(0.01)
+  .onChange(sync);
+lightingFolder.add(options, "ambient", 0, 1).onChange(sync);
+lightingFolder.add(options, "diffuse", 0, 1).onChange(sync);
+lightingFolder.add(options, "specular", 0, 1).onChange(sync);
+lightingFolder.add(options, "shininess", 1, 200).onChange(sync);
 
-const { origin, scale } = frustal.current_domain();
+const { x, y, scale } = frustal.current_domain();
 const view = {
-  x: origin.x,
-  y: origin.y,
-  scale: scale
+  x,
+  y,
+  scale
 };
 
 const updateDomain = () => {
-  const { origin, scale } = frustal.current_domain();
-  view.x = origin.x;
-  view.y = origin.y;
+  const { x, y, scale } = frustal.current_domain();
+  view.x = x;
+  view.y = y;
   view.scale = scale;
   gui.__controllers.map(c => c.updateDisplay());
 };
 
 gui.remember(options);
 const syncDomain = debounce((...args) => {
-  frustal.change_domain(view.x, view.y, view.scale);
+  frustal.change_domain(String(view.x), String(view.y), String(view.scale));
   render();
 }, 25);
 
-gui
-  .add(view, "x")
-  .step(0.000001)
-  .onChange(syncDomain);
-gui
-  .add(view, "y")
-  .step(0.000001)
-  .onChange(syncDomain);
-gui
-  .add(view, "scale")
-  .min(0)
-  .step(0.000001)
-  .onChange(syncDomain);
+gui.add(view, "x").onChange(syncDomain);
+gui.add(view, "y").onChange(syncDomain);
+gui.add(view, "scale").onChange(syncDomain);
 
 gui.remember(view);
 
-gui.add(jsOptions, "preview").onChange(() => {
-  previewCanvas
-    .getContext("2d")
-    .clearRect(0, 0, previewCanvas.width, previewCanvas.height);
-});
+gui.add(jsOptions, "preview");
+gui.add(jsOptions, "previewUpscale", Upscale);
 
 gui
   .add(jsOptions, "previewScale")
@@ -374,19 +488,192 @@ gui
   .max(20)
   .step(1)
   .onChange(
-    debounce(() => {
-      previewCanvas.width = Math.floor(
-        mainCanvas.width / jsOptions.previewScale
-      );
-      previewCanvas.height = Math.floor(
-        mainCanvas.height / jsOptions.previewScale
-      );
-      frustal.resize_preview(jsOptions.previewScale);
-    }, 10)
+    debounce(() => frustal.resize_preview(jsOptions.previewScale), 10)
   );
 
+gui.add(jsOptions, "renderBudget", 1, 200).step(1);
+gui.add(jsOptions, "cycleSpeed", -2, 2).step(0.01);
+
+const antialiasFolder = gui.addFolder("Antialiasing");
+antialiasFolder.add(jsOptions, "antialias").onChange(render);
+antialiasFolder.add(jsOptions, "sampling", Sampling).onChange(render);
+antialiasFolder
+  .add(jsOptions, "samples", 1, 8)
+  .step(1)
+  .onChange(render);
+antialiasFolder.add(jsOptions, "filter", Filter).onChange(render);
+antialiasFolder.add(jsOptions, "threshold", 0, 1).onChange(render);
+
 gui.remember(jsOptions);
 
+// Stops are `color position` separated by `;`, empty for hue rotation
+const palette = {
+  stops: "",
+  interpolation: Interpolation.Linear,
+  space: ColorSpace.Rgb,
+  wrap: Wrap.Repeat,
+  offset: 0,
+  density: 1
+};
+
+const syncPalette = debounce(() => {
+  const current = palette.stops ? Palette.parse(palette.stops) : undefined;
+  if (palette.stops && !current) {
+    return;
+  }
+  if (current) {
+    current.interpolation = palette.interpolation;
+    current.space = palette.space;
+    current.wrap = palette.wrap;
+    current.offset = palette.offset;
+    current.density = palette.density;
+  }
+  frustal.set_palette(current);
+  frustal.recolor();
+  redraw();
+}, 25);
+
+const paletteFolder = gui.addFolder("Palette");
+paletteFolder.add(palette, "stops").onChange(syncPalette);
+paletteFolder
+  .add(palette, "interpolation", Interpolation)
+  .onChange(syncPalette);
+paletteFolder.add(palette, "space", ColorSpace).onChange(syncPalette);
+paletteFolder.add(palette, "wrap", Wrap).onChange(syncPalette);
+const paletteOffset = paletteFolder
+  .add(palette, "offset")
+  .step(0.001)
+  .onChange(syncPalette);
+paletteFolder
+  .add(palette, "density")
+  .step(0.0001)
+  .onChange(syncPalette);
+
+const paletteFiles = {
+  import: () => {
+    const input = document.createElement("input");
+    input.type = "file";
+    input.accept = ".ggr,.map,.ugr";
+    input.addEventListener("change", async () => {
+      const [file] = input.files;
+      const text = await file.text();
+      const parse = {
+        ggr: Palette.from_ggr,
+        map: Palette.from_map,
+        ugr: Palette.from_ugr
+      }[file.name.split(".").pop().toLowerCase()];
+      const imported = parse && parse(text);
+      if (!imported) {
+        console.error(`Unable to import palette ${file.name}`);
+        return;
+      }
+      palette.stops = imported.description();
+      palette.interpolation = imported.interpolation;
+      imported.free();
+      paletteFolder.__controllers.map(c => c.updateDisplay());
+      syncPalette();
+    });
+    input.click();
+  },
+  export: () => {
+    const current = Palette.parse(palette.stops);
+    if (!current) {
+      return;
+    }
+    current.interpolation = palette.interpolation;
+    current.space = palette.space;
+    current.wrap = palette.wrap;
+    const link = document.createElement("a");
+    link.href = URL.createObjectURL(
+      new Blob([current.to_map()], { type: "text/plain" })
+    );
+    current.free();
+    link.download = "frustals.map";
+    link.click();
+    URL.revokeObjectURL(link.href);
+  }
+};
+paletteFolder.add(paletteFiles, "import");
+paletteFolder.add(paletteFiles, "export");
+
+gui.remember(palette);
+
+// Newton root colours as `#rrggbb` separated by `;`, empty ones keep the hue
+const roots = {
+  colors: ""
+};
+
+const syncRoots = debounce(() => {
+  frustal.reset_root_colors();
+  roots.colors.split(";").forEach((color, root) => {
+    const match = /^#([0-9a-f]{2})([0-9a-f]{2})([0-9a-f]{2})$/i.exec(
+      color.trim()
+    );
+    if (match && root < frustal.channels()) {
+      const [r, g, b] = match.slice(1).map(c => parseInt(c, 16));
+      frustal.set_root_color(root, r, g, b);
+    }
+  });
+  frustal.recolor();
+  redraw();
+}, 25);
+
+const rootsFolder = gui.addFolder("Roots");
+rootsFolder.add(roots, "colors").onChange(syncRoots);
+
+gui.remember(roots);
+
+// Layers replace the single colouring when any source is set
+const layers = [0, 1, 2].map(() => ({
+  source: -1,
+  blend: Blend.Normal,
+  opacity: 1
+}));
+
+const syncLayers = debounce(() => {
+  frustal.clear_layers();
+  // Dropdowns give back strings
+  layers
+    .filter(({ source }) => Number(source) >= 0)
+    .forEach(({ source, blend, opacity }) =>
+      frustal.add_layer(Layer.new(Number(source), Number(blend), opacity))
+    );
+  frustal.recolor();
+  redraw();
+}, 25);
+
+const layersFolder = gui.addFolder("Layers");
+layers.forEach((layer, i) => {
+  layersFolder
+    .add(layer, "source", { None: -1, ...LayerSource })
+    .name(`layer ${i + 1}`)
+    .onChange(syncLayers);
+  layersFolder.add(layer, "blend", Blend).onChange(syncLayers);
+  layersFolder
+    .add(layer, "opacity", 0, 1)
+    .step(0.01)
+    .onChange(syncLayers);
+  gui.remember(layer);
+});
+
+// Colour cycling only recolors the stored results, keeping the gui values
+// in step with the ones advanced by the frustal
+let lastCycle = null;
+const cycle = time => {
+  if (jsOptions.cycleSpeed && lastCycle !== null) {
+    const step = (jsOptions.cycleSpeed * (time - lastCycle)) / 1000;
+    frustal.cycle(step);
+    options.color_base += 360 * step;
+    palette.offset += step;
+    colorBase.updateDisplay();
+    paletteOffset.updateDisplay();
+    redraw();
+  }
+  lastCycle = time;
+  requestAnimationFrame(cycle);
+};
+requestAnimationFrame(cycle);
+
 gui.revert();
 gui.__preset_select.addEventListener("change", ({ target: { value } }) => {
   location.hash = `#${encodeURIComponent(value)}`;
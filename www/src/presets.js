@@ -42,6 +42,56 @@ export default {
         scale: 0.0028
       }
     },
+    "Deep dendrite": {
+      "0": {
+        variant: 0,
+        precision: 4000,
+        smooth: true,
+        order: 2,
+        const_real: 0,
+        const_imaginary: 0,
+        colorization: 1,
+        black_threshold: 1000,
+        white_threshold: 100000,
+        overexposure: 1,
+        color_base: 200,
+        color_rotation: 0.5
+      },
+      "1": {
+        x: "0",
+        y: "1",
+        scale: "1e-400"
+      }
+    },
+    "Gradient Mandelbrot": {
+      "0": {
+        variant: 0,
+        precision: 300,
+        smooth: true,
+        order: 2,
+        const_real: 0,
+        const_imaginary: 0,
+        colorization: 1,
+        black_threshold: 0,
+        white_threshold: 100000,
+        overexposure: 1,
+        color_base: 0,
+        color_rotation: 0.5
+      },
+      "1": {
+        x: -0.75,
+        y: 0,
+        scale: 1.5
+      },
+      "3": {
+        stops:
+          "#000764 0; #206bcb .16; #edffff .42; #ffaa00 .6425; #000200 .8575",
+        interpolation: 3,
+        wrap: 0,
+        offset: 0,
+        density: 0.05
+      }
+    },
     "Multibrot 3": {
       "0": {
         variant: 0,